@@ -1,4 +1,7 @@
 
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
 const N: usize = 3;
 
 pub fn new_elf(mut new_val: f64, top_elves: &mut [f64; N]) {
@@ -19,26 +22,91 @@ pub fn sum(top_elves: &[f64; N]) -> f64 {
     val
 }
 
-pub fn run() {
-    let src = std::fs::read_to_string("data/d1_actual.txt").expect("Failed to read input");
-
+/// Returns the calories carried by the top N elves, fattest first
+fn parse_top_elves(src: &str) -> Result<[f64; N], ParseError> {
     // Assume no elves means zero calories (what is the maximum of the empty set?)
     let mut top_elves = [0.0; N];
     let mut this = 0.0; 
-    for line in src.split("\n") {
-        match line {
-            "" => {
+    let mut it = get_byte_iterator(src);
+    loop {
+        match it.peek() {
+            None => {
+                new_elf(this, &mut top_elves);
+                return Ok(top_elves);
+            },
+            Some((_, b'\n')) => {
+                it.next();
                 new_elf(this, &mut top_elves);
                 this = 0.0;
             },
-            str_val => {
-                let val =
-                    str_val.parse::<f64>()
-                    .expect("Expected only blank lines and numbers");
+            Some(_) => {
+                let val: f64 = parse_unsigned(src, &mut it)?;
+                expect_line_end(src, &mut it)?;
                 this += val;
             }
         }
     }
-    println!("The fattest hobbit has {} Calories", top_elves[0]);
-    println!("The fattest {} hobbitses has {} Calories", N, sum(&top_elves));
+}
+
+#[derive(Default)]
+pub struct Day1;
+
+impl Solution for Day1 {
+    type Input = [f64; N];
+    type Answer1 = f64;
+    type Answer2 = f64;
+
+    fn parse(&self, source: &str) -> Result<[f64; N], ParseError> {
+        parse_top_elves(source)
+    }
+
+    /// The fattest hobbit
+    fn part1(&self, top_elves: &[f64; N]) -> f64 {
+        top_elves[0]
+    }
+
+    /// The fattest N hobbitses
+    fn part2(&self, top_elves: &[f64; N]) -> f64 {
+        sum(top_elves)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "1000
+2000
+3000
+
+4000
+
+5000
+6000
+
+7000
+8000
+9000
+
+10000
+";
+
+    #[test]
+    fn test_example() {
+        let top_elves = Day1.parse(EXAMPLE).unwrap();
+        assert_eq!(Day1.part1(&top_elves), 24000.0);
+        assert_eq!(Day1.part2(&top_elves), 45000.0);
+
+        // The last elf counts without a trailing newline too
+        let top_elves = Day1.parse("1000\n\n3000").unwrap();
+        assert_eq!(Day1.part1(&top_elves), 3000.0);
+        assert_eq!(Day1.part2(&top_elves), 4000.0);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = Day1.parse("1000\n2x00\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+    }
 }
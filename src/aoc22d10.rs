@@ -1,38 +1,48 @@
 
 
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Instruction {
+    Noop,
+    Addx(isize),
+}
+
+fn parse_instruction(src: &str, it: &mut ByteIterator) -> Result<Instruction, ParseError> {
+    if optional(src, it, |src, it| expect_sequence(src, it, "noop")).is_some() {
+        return Ok(Instruction::Noop);
+    }
+    expect_sequence(src, it, "addx ")?;
+    Ok(Instruction::Addx(parse_signed(src, it)?))
+}
+
+fn parse(source: &str) -> Result<Vec<Instruction>, ParseError> {
+    parse_lines(source, parse_instruction)
+}
+
 /// Determine the cycle number inside the current set
 fn cycle_mod(cycle: isize) -> isize {
     (cycle + 20) % 40
 }
 
-fn solve(input: &str) -> isize {
+fn solve(instructions: &Vec<Instruction>) -> isize {
     let mut output = 0;
     let mut x_val = 1;
     let mut cycle = 0;
-    for line in input.split("\n") {
-        if line == "" { continue; }
-
-        // Parse and move cycle forward
+    for instruction in instructions {
+        // Move cycle forward
         let old_cycle = cycle;
-        let mut tok_it = line.split(" ");
-        let cmd = tok_it.next().expect("Expected at least one token");
-        let delta = match cmd {
-            "addx" => {
+        let delta = match instruction {
+            Instruction::Addx(v) => {
                 cycle += 2;
-                tok_it.next()
-                    .expect("Expected at least one token")
-                    .parse()
-                    .expect("Failed to parse addx instruction")
+                *v
             },
-            "noop" => {
+            Instruction::Noop => {
                 cycle += 1;
                 0
             },
-            token => {
-                panic!("Unexpected command {}", token);
-            }
         };
-        assert!(matches!(tok_it.next(), None), "Extra token found");
 
         // Check passed point
         //  - when the number rolls around that's a decrease and a set complete
@@ -48,60 +58,66 @@ fn solve(input: &str) -> isize {
 }
 
 
-fn check_draw(x_val: isize, cycle: isize) {
+fn check_draw(x_val: isize, cycle: isize, screen: &mut String) {
     let cycle_pos = cycle % 40;
     if x_val <= cycle_pos && cycle_pos <= x_val + 2 {
-        print!("#");
+        screen.push('#');
     } else {
-        print!(" ");
+        screen.push(' ');
     }
     if cycle % 40 == 0 {
-        print!("\n");
+        screen.push('\n');
     }
 }
 
-fn solve2(input: &str) {
+/// Returns what is drawn on the CRT
+fn solve2(instructions: &Vec<Instruction>) -> String {
+    let mut screen = String::new();
     let mut x_val = 1;
     let mut cycle = 0;
-    for line in input.split("\n") {
-        if line == "" { continue; }
-
-        // Parse and move cycle forward
-        let mut tok_it = line.split(" ");
-        let cmd = tok_it.next().expect("Expected at least one token");
-        let delta = match cmd {
-            "addx" => {
+    for instruction in instructions {
+        // Move cycle forward
+        let delta = match instruction {
+            Instruction::Addx(v) => {
                 cycle += 1;
-                check_draw(x_val, cycle);
+                check_draw(x_val, cycle, &mut screen);
                 cycle += 1;
-                check_draw(x_val, cycle);
-                tok_it.next()
-                    .expect("Expected at least one token")
-                    .parse()
-                    .expect("Failed to parse addx instruction")
+                check_draw(x_val, cycle, &mut screen);
+                *v
             },
-            "noop" => {
+            Instruction::Noop => {
                 cycle += 1;
-                check_draw(x_val, cycle);
+                check_draw(x_val, cycle, &mut screen);
                 0
             },
-            token => {
-                panic!("Unexpected command {}", token);
-            }
         };
-        assert!(matches!(tok_it.next(), None), "Extra token found");
 
         // Change register
         x_val += delta;
     }
+    screen
 }
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d10.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input));
-    solve2(&input);
+#[derive(Default)]
+pub struct Day10;
+
+impl Solution for Day10 {
+    type Input = Vec<Instruction>;
+    type Answer1 = isize;
+    type Answer2 = String;
+
+    fn parse(&self, source: &str) -> Result<Vec<Instruction>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, instructions: &Vec<Instruction>) -> isize {
+        solve(instructions)
+    }
+
+    /// The answer is read off the CRT, so start it on its own line
+    fn part2(&self, instructions: &Vec<Instruction>) -> String {
+        format!("\n{}", solve2(instructions))
+    }
 }
 
 
@@ -257,6 +273,12 @@ noop
 noop";
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE), 13140);
+        assert_eq!(solve(&parse(EXAMPLE).unwrap()), 13140);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("noop\naddx\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 5));
     }
 }
@@ -1,7 +1,11 @@
 
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
 type Item = u64;
 
-struct Monkey {
+#[derive(Clone,Debug)]
+pub struct Monkey {
     inspection_count: usize,
     items: Vec<Item>,
     operator: Operator,
@@ -12,161 +16,116 @@ struct Monkey {
     throw_if_false: usize,
 }
 
-macro_rules! consume {
-    ($it:ident, $token:pat_param, $msg: expr) => ({
-        match $it.next() {
-            Some($token) => { },
-            Some(tok) => {
-                panic!("Expected {} but received {}", $msg, tok);
-            },
-            None => {
-                panic!("Expected {} but line ended unexpectedly", $msg);
-            }
-        }
-    });
-}
-
-fn assert_eol(token: Option<&str>) {
-    match token {
-        Some(tok) => {
-            panic!("Expected end of line, not '{}'", tok);
-        },
-        None => { },
-    }
-}
-
+#[derive(Clone,Copy,Debug)]
 enum Operator {
     Mult,
     Plus,
 }
 use Operator::*;
 
-#[derive(Clone,Copy)]
+#[derive(Clone,Copy,Debug)]
 enum Operand {
     Old,
     Val(Item),
 }
 use Operand::*;
 
-fn parse_operand(token: &str) -> Operand {
-    if token == "old" {
-        Old
+fn parse_operand(src: &str, it: &mut ByteIterator) -> Result<Operand, ParseError> {
+    if optional(src, it, |src, it| expect_sequence(src, it, "old")).is_some() {
+        Ok(Old)
     } else {
-        Val(token.parse().expect("Failed to parse operand"))
+        Ok(Val(parse_unsigned(src, it)?))
     }
 }
 
-fn parse_operator(token: &str) -> Operator {
-    match token {
-        "+" => Plus,
-        "*" => Mult,
-        _ => {
-            panic!("Unexpected operator {}", token);
-        },
-    }
+/// Parses a line of the form "<prefix><number>\n"
+fn parse_number_line<T: std::str::FromStr>(src: &str, it: &mut ByteIterator, prefix: &str)
+    -> Result<T, ParseError>
+{
+    expect_sequence(src, it, prefix)?;
+    let val = parse_unsigned(src, it)?;
+    expect_line_end(src, it)?;
+    Ok(val)
 }
 
-fn get_number_at_end
-    <T: std::str::FromStr + std::fmt::Debug>
-    (prefix: &str, line: &str)
-    -> T
-    where <T as std::str::FromStr>::Err: std::fmt::Debug
+/// Like parse_number_line, but also returns where the number starts
+fn parse_target_line(src: &str, it: &mut ByteIterator, prefix: &str)
+    -> Result<(usize, usize), ParseError>
 {
-    if &line[0..prefix.len()] != prefix {
-        panic!("Expected prefix of '{:?}', but got '{:?}'", prefix, line);
-    }
-    line[prefix.len()+1..line.len()]
-        .parse()
-        .expect("Failed to parse number")
+    expect_sequence(src, it, prefix)?;
+    let start = match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    };
+    let target = parse_unsigned(src, it)?;
+    expect_line_end(src, it)?;
+    Ok((target, start))
 }
 
+/// Also returns the offsets of the two throw targets, which can only be checked
+/// once every monkey has been parsed
+fn parse_monkey(src: &str, it: &mut ByteIterator, monkey_num: usize)
+    -> Result<(Monkey, [usize; 2]), ParseError>
+{
+    expect_sequence(src, it, &format!("Monkey {}:", monkey_num))?;
+    expect_line_end(src, it)?;
+
+    expect_sequence(src, it, "  Starting items: ")?;
+    let monkey_items = separated(src, it, ", ", parse_unsigned)?;
+    expect_line_end(src, it)?;
+
+    expect_sequence(src, it, "  Operation: new = ")?;
+    let left = parse_operand(src, it)?;
+    let op = one_of(src, it, &[(" + ", Plus), (" * ", Mult)])?;
+    let right = parse_operand(src, it)?;
+    expect_line_end(src, it)?;
+
+    let divisor = parse_number_line(src, it, "  Test: divisible by ")?;
+    let (if_true, true_start) = parse_target_line(src, it, "    If true: throw to monkey ")?;
+    let (if_false, false_start) = parse_target_line(src, it, "    If false: throw to monkey ")?;
+
+    let monkey = Monkey {
+        inspection_count: 0,
+        items: monkey_items,
+        operator: op,
+        left_operand: left,
+        right_operand: right,
+        test_divisor: divisor,
+        throw_if_true: if_true,
+        throw_if_false: if_false,
+    };
+    Ok((monkey, [true_start, false_start]))
+}
 
-/// Determine the cycle number inside the current set
-fn parse(source: &str) -> Vec<Monkey> {
-    let mut src_it = source.split("\n").peekable();
-    let mut monkey_num = 0;
+/// Monkeys are blocks of lines separated by blank lines
+fn parse(source: &str) -> Result<Vec<Monkey>, ParseError> {
+    let mut it = get_byte_iterator(source);
     let mut monkeys = Vec::new();
+    let mut target_starts = Vec::new();
     loop {
-        match src_it.next() {
-            Some(name_line) => {
-                if name_line != format!("Monkey {}:", monkey_num) {
-                    panic!("Unexpected form for monkey declaration");
-                }
-            },
-            None => { panic!("Expected monkey declaration"); }
-        }
-        monkey_num += 1;
-
-        let mut line_it = src_it.next().expect("Expected Starting items").split(" ");
-        consume!(line_it, "", "<empty string>");
-        consume!(line_it, "", "<empty string>");
-        consume!(line_it, "Starting", "\"Starting\"");
-        consume!(line_it, "items:", "\"items:\"");
-        let mut monkey_items = Vec::new();
-        loop {
-            match line_it.next() {
-                Some(val) => {
-                    monkey_items.push(
-                        if &val[val.len()-1..val.len()] == "," {
-                            val[0..val.len()-1]
-                                .parse()
-                                .expect("Expected number for worry level")
-                        } else {
-                            val
-                                .parse()
-                                .expect("Expected number for worry level")
-                        });
-                },
-                None => { break; }
-            }
+        // Consume blank lines
+        while try_consume_sequence(source, &mut it, "\n")? { }
+        if it.peek().is_none() {
+            break;
         }
-        assert_eol(line_it.next());
-
-        let mut line_it = src_it.next().expect("Expected operation items").split(" ");
-        consume!(line_it, "", "<empty string>");
-        consume!(line_it, "", "<empty string>");
-        consume!(line_it, "Operation:", "\"Operation:\"");
-        consume!(line_it, "new", "\"new\"");
-        consume!(line_it, "=", "\"=\"");
-        let left = parse_operand(line_it.next().expect("Expected left operand"));
-        let op = parse_operator(line_it.next().expect("Expected operator"));
-        let right = parse_operand(line_it.next().expect("Expected right operand"));
-        assert_eol(line_it.next());
-
-        let divisor = get_number_at_end(
-            "  Test: divisible by",
-            src_it.next().expect("Expected test line"));
-        
-        let if_true = get_number_at_end(
-            "    If true: throw to monkey",
-            src_it.next().expect("Expected throw line (true)"));
-        
-        let if_false = get_number_at_end(
-            "    If false: throw to monkey",
-            src_it.next().expect("Expected throw line (false)"));
-
-        monkeys.push(
-            Monkey {
-                inspection_count: 0,
-                items: monkey_items, 
-                operator: op,
-                left_operand: left,
-                right_operand: right,
-                test_divisor: divisor,
-                throw_if_true: if_true,
-                throw_if_false: if_false,
-            });
+        let (monkey, starts) = parse_monkey(source, &mut it, monkeys.len())?;
+        monkeys.push(monkey);
+        target_starts.push(starts);
+    }
 
-        // Consume blank lines
-        loop {
-            match src_it.peek() {
-                Some(&"") => { src_it.next(); },
-                Some(_) => { break; }
-                None => { return monkeys; }
+    // A monkey throwing to itself would never empty its hands
+    let n_monkeys = monkeys.len();
+    for (i, (monkey, starts)) in monkeys.iter().zip(target_starts).enumerate() {
+        for (target, start) in [(monkey.throw_if_true, starts[0]), (monkey.throw_if_false, starts[1])] {
+            if target >= n_monkeys || target == i {
+                return Err(ParseError::new(
+                    source, start,
+                    &format!("a monkey from 0 to {} other than {}", n_monkeys - 1, i),
+                    &target.to_string()));
             }
         }
     }
-
+    Ok(monkeys)
 }
 
 fn add_lcm_factor(lcm: Item, factor: Item) -> Item {
@@ -231,8 +190,8 @@ fn execute_round(monkeys: &mut Vec<Monkey>, relief: Item, lcm: Item) {
     }
 }
 
-fn solve(input: &str, relief: Item, rounds: usize) -> usize {
-    let mut monkeys = parse(input);
+fn solve(monkeys: &[Monkey], relief: Item, rounds: usize) -> usize {
+    let mut monkeys = monkeys.to_vec();
     let lcm = get_monkeys_least_common_multiple(&monkeys);
     for _ in 0..rounds {
         execute_round(&mut monkeys, relief, lcm);
@@ -256,12 +215,40 @@ fn solve(input: &str, relief: Item, rounds: usize) -> usize {
 }
 
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d11.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input, 3, 20));
-    println!("{}", solve(&input, 1, 10000));
+pub struct Day11 {
+    pub relief_p1: Item,
+    pub rounds_p1: usize,
+    pub relief_p2: Item,
+    pub rounds_p2: usize,
+}
+
+impl Default for Day11 {
+    fn default() -> Day11 {
+        Day11 {
+            relief_p1: 3,
+            rounds_p1: 20,
+            relief_p2: 1,
+            rounds_p2: 10000,
+        }
+    }
+}
+
+impl Solution for Day11 {
+    type Input = Vec<Monkey>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Vec<Monkey>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, monkeys: &Vec<Monkey>) -> usize {
+        solve(monkeys, self.relief_p1, self.rounds_p1)
+    }
+
+    fn part2(&self, monkeys: &Vec<Monkey>) -> usize {
+        solve(monkeys, self.relief_p2, self.rounds_p2)
+    }
 }
 
 
@@ -298,7 +285,28 @@ Monkey 3:
     If false: throw to monkey 1";
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE, 3, 20), 10605);
-        assert_eq!(solve(EXAMPLE, 1, 10000), 2713310158);
+        let monkeys = parse(EXAMPLE).unwrap();
+        assert_eq!(solve(&monkeys, 3, 20), 10605);
+        assert_eq!(solve(&monkeys, 1, 10000), 2713310158);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let src = EXAMPLE.replace("new = old + 6", "new = old - 6");
+        let err = parse(&src).unwrap_err();
+        assert_eq!((err.line, err.column), (10, 23));
+        assert_eq!(err.expected, "one of \" + \", \" * \"");
+    }
+
+    #[test]
+    fn test_bad_target() {
+        let src = EXAMPLE.replace("If false: throw to monkey 0", "If false: throw to monkey 4");
+        let err = parse(&src).unwrap_err();
+        assert_eq!((err.line, err.column), (13, 31));
+        assert_eq!(err.expected, "a monkey from 0 to 3 other than 1");
+
+        let src = EXAMPLE.replace("If true: throw to monkey 1", "If true: throw to monkey 2");
+        let err = parse(&src).unwrap_err();
+        assert_eq!((err.line, err.column), (19, 30));
     }
 }
@@ -1,286 +1,118 @@
 
-type Distance = u16;
+use crate::arr2d::Array2d;
+use crate::search;
+use crate::solution::Solution;
+use crate::parse_utils::ParseError;
+
+type Distance = search::Cost;
 
 const Z_VAL: i8 = b'z' as i8 - b'a' as i8;
 const S_VAL: i8 = b'S' as i8 - b'a' as i8;
 const E_VAL: i8 = b'E' as i8 - b'a' as i8;
 
-/// Two dimensional rectangular array of size (I x J)
-///
-/// First dimension is the fastest moving index, so:
-///  - Fast to iterate along first dimension (I)
-///  - Fast to expand along second dimension (J)
-///
-/// Uses isize so we can do arithmetic around coordinates
-struct Array2d<T: Copy> {
-    data: Vec<T>,
-    size_i: usize,
-    size_j: usize,
+/// Heights (0 for 'a' to 25 for 'z') plus where we start and finish
+pub struct HeightMap {
+    height: Array2d<i8>,
+    start: (isize,isize),
+    end: (isize,isize),
 }
 
-impl<T: Copy> Array2d<T> {
-    fn new(initial: T, size_i: usize, size_j: usize) -> Array2d<T> {
-        let mut data = Vec::with_capacity(size_i*size_j);
-        for _ in 0..(size_i*size_j) {
-            data.push(initial);
-        }
-        Array2d {
-            data: data,
-            size_i: size_i,
-            size_j: size_j,
-        }
-    }
-    
-    fn new_empty() -> Array2d<T> {
-        Array2d {
-            data: Vec::new(),
-            size_i: 0,
-            size_j: 0,
-        }
-    }
-
-    fn get(&self, (i, j): (isize, isize)) -> &T {
-        &self.data[i as usize + self.size_i*j as usize]
-    }
-
-    fn get_mut(&mut self, (i, j): (isize, isize)) -> &mut T {
-        &mut self.data[i as usize + self.size_i*j as usize]
-    }
-    
-    /// Create a new row (add 1 to second dimension)
-    /// 
-    ///  - Fills with items in iterator
-    ///  - Either size_i == size_j == 0 or iterator must provide exactly size_i
-    ///    elements.
-    fn add_row<I: std::iter::Iterator<Item=T>>(&mut self, iterator: I) {
-        self.data.reserve(self.size_i);
-
-        let mut count = 0;
-        for item in iterator {
-            count += 1;
-            self.data.push(item);
-        }
-        if self.size_i == 0 && self.size_j == 0 {
-            self.size_i = count;
-        } else if count != self.size_i {
-            panic!("Unexpect number of items added to Array2d");
+/// Finds the only occurrence of marker, which must exist
+fn find_marker(source: &str, marker: char, name: &str) -> Result<usize, ParseError> {
+    let mut found = source.match_indices(marker).map(|(offset, _)| offset);
+    let expected = format!("{} '{}'", name, marker);
+    let offset = match found.next() {
+        Some(offset) => offset,
+        None => {
+            return Err(ParseError::new(source, source.len(), &expected, "end of input"));
         }
-        self.size_j += 1;
+    };
+    match found.next() {
+        Some(second) => {
+            let expected = format!("no second '{}'", marker);
+            Err(ParseError::new(source, second, &expected, &format!("'{}'", marker)))
+        },
+        None => Ok(offset),
     }
 }
 
-
-fn parse(source: &str) -> Array2d<i8>  {
-    let mut height = Array2d::new_empty();
-    for line in source.split("\n") {
-        if line != "" {
-            height.add_row(line.bytes().map(|x| (x as i8) - (b'a' as i8)));
+fn parse(source: &str) -> Result<HeightMap, ParseError> {
+    let mut height = Array2d::try_from_text(source, |c| {
+        if c.is_ascii_lowercase() || c == 'S' || c == 'E' {
+            Some((c as i8) - (b'a' as i8))
+        } else {
+            None
         }
-    }
-    height
-}
-
-fn find_start_end(height: &mut Array2d<i8>) -> ((isize,isize),(isize,isize)) {
-    let mut start = None;
-    let mut end = None;
-    for i in 0..(height.size_i as isize) {
-        for j in 0..(height.size_j as isize) {
-            let h = height.get_mut((i,j));
-            if *h == S_VAL {
-                *h = 0;
-                if start == None {
-                    start = Some((i,j));
-                } else {
-                    panic!("Found two starts");
-                }
-            } else if *h == E_VAL {
-                *h = Z_VAL;
-                if end == None {
-                    end = Some((i,j));
-                } else {
-                    panic!("Found two ends");
-                }
-            }
+    })?;
+    find_marker(source, 'S', "a start")?;
+    find_marker(source, 'E', "an end")?;
+
+    let mut start = (0,0);
+    let mut end = (0,0);
+    for coord in height.coords() {
+        let h = height.get_mut(coord);
+        if *h == S_VAL {
+            *h = 0;
+            start = coord;
+        } else if *h == E_VAL {
+            *h = Z_VAL;
+            end = coord;
         }
     }
-    let start = start.expect("Couldn't find start");
-    let end = end.expect("Couldn't find end");
-    (start, end)
+    Ok(HeightMap { height, start, end })
 }
 
 
-/// Depth first search
-///
-/// Given we can move to the current location in dist steps, look at all
-/// cardinal directions around to see if it leads to the end
-fn find_shortest_path(
-        height: &Array2d<i8>,
-        distances: &mut Array2d<Distance>,
-        dist: Distance,
-        mut best: Distance,
-        current: (isize,isize),
-        end: (isize,isize))
-    -> Distance 
-{
-    // Check if we've gone further than required
-    if dist >= best {
-        return best;
-    }
-
-    // Check if we've arrived
-    if current == end {
-        return dist;
-    }
-
-    // Update the distance map
-    {
-        let d = distances.get_mut(current);
-        if *d <= dist {
-            // We can get there faster by another route
-            return best;
-        } else {
-            // We've found the fastest route to the here
-            *d = dist;
-        }
-    }
-
-    // Search around
-    let h = height.get(current);
-    let (i,j) = current;
-    for (di, dj) in [(-1, 0), (0, 1), (1, 0), (0, -1)] {
-        let i_new = i + di;
-        let j_new = j + dj;
-        // Will we move off the map?
-        if i_new < 0 { continue; }
-        if j_new < 0 { continue; }
-        if i_new as usize >= height.size_i { continue; }
-        if j_new as usize >= height.size_j { continue; }
-        // Try move there
-        let new = (i_new, j_new);
-        let h_new = *height.get(new);
-        if h_new > h + 1 {
-            continue; // Too high a jump
-        }
-        best = 
-            find_shortest_path(
-                height,
-                distances,
-                dist + 1,
-                best,
-                (i_new, j_new),
-                end);
-    }
-    return best;
+/// Can we step from one height to the other? We can climb at most one
+fn can_step(from: i8, to: i8) -> bool {
+    to <= from + 1
 }
 
+/// Breadth first search from the start to the end
+fn solvep1(map: &HeightMap) -> Distance {
+    let height = &map.height;
+    let result = search::bfs(
+        [map.start],
+        |current| {
+            let h = *height.get(current);
+            height.neighbours4(current).filter(move |n| can_step(h, *height.get(*n)))
+        },
+        |current| current == map.end);
+    result.goal_distance().expect("Couldn't find shortest path")
+}
 
-
-fn solvep1(input: &str) -> Distance {
-    let mut height = parse(input);
-    let (start, end) = find_start_end(&mut height);
-    let mut distances = Array2d::new(Distance::MAX, height.size_i, height.size_j);
-    let dist = find_shortest_path(
-        &height,
-        &mut distances,
-        0,
-        Distance::MAX,
-        start,
-        end);
-    if dist == Distance::MAX {
-        panic!("Couldn't find shortest path");
-    }
-    dist
+/// Breadth first search from every lowest point at once
+fn solvep2(map: &HeightMap) -> Distance {
+    let height = &map.height;
+    let result = search::bfs(
+        height.coords().filter(|c| *height.get(*c) == 0),
+        |current| {
+            let h = *height.get(current);
+            height.neighbours4(current).filter(move |n| can_step(h, *height.get(*n)))
+        },
+        |current| current == map.end);
+    result.goal_distance().expect("Couldn't find shortest path")
 }
 
-/// Depth first search
-///
-/// Given we can move to the current location in dist steps, look at all
-/// cardinal directions around to see if it leads to the end
-///
-/// We are just looking for a path to a level of 0, so no destination known
-///
-/// Rules for movement are opposite part 1 as we're backtracking
-fn find_shortest_path_p2(
-        height: &Array2d<i8>,
-        distances: &mut Array2d<Distance>,
-        dist: Distance,
-        mut best: Distance,
-        current: (isize,isize))
-    -> Distance 
-{
-    // Check if we've gone further than required
-    if dist >= best {
-        return best;
-    }
+#[derive(Default)]
+pub struct Day12;
 
-    // Check if we've arrived
-    let h = *height.get(current);
-    if h == 0 {
-        return dist;
-    }
+impl Solution for Day12 {
+    type Input = HeightMap;
+    type Answer1 = Distance;
+    type Answer2 = Distance;
 
-    // Update the distance map
-    {
-        let d = distances.get_mut(current);
-        if *d <= dist {
-            // We can get there faster by another route
-            return best;
-        } else {
-            // We've found the fastest route to the here
-            *d = dist;
-        }
+    fn parse(&self, source: &str) -> Result<HeightMap, ParseError> {
+        parse(source)
     }
 
-    // Search around
-    let (i,j) = current;
-    for (di, dj) in [(-1, 0), (0, 1), (1, 0), (0, -1)] {
-        let i_new = i + di;
-        let j_new = j + dj;
-        // Will we move off the map?
-        if i_new < 0 { continue; }
-        if j_new < 0 { continue; }
-        if i_new as usize >= height.size_i { continue; }
-        if j_new as usize >= height.size_j { continue; }
-        // Try move there
-        let new = (i_new, j_new);
-        let h_new = *height.get(new);
-        if h > h_new + 1 {
-            continue; // Too high a jump
-        }
-        best =
-            find_shortest_path_p2(
-                height,
-                distances,
-                dist + 1,
-                best,
-                (i_new, j_new));
+    fn part1(&self, map: &HeightMap) -> Distance {
+        solvep1(map)
     }
-    return best;
-}
-
-fn solvep2(input: &str) -> Distance {
-    let mut height = parse(input);
-    let (_, end) = find_start_end(&mut height);
-    let mut distances = Array2d::new(Distance::MAX, height.size_i, height.size_j);
-    let dist = find_shortest_path_p2(
-        &height,
-        &mut distances,
-        0,
-        Distance::MAX,
-        end);
 
-    if dist == Distance::MAX {
-        panic!("Couldn't find shortest path");
+    fn part2(&self, map: &HeightMap) -> Distance {
+        solvep2(map)
     }
-    dist
-}
-
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d12.txt")
-        .expect("Failed to read input");
-    println!("{}", solvep1(&input));
-    println!("{}", solvep2(&input));
 }
 
 
@@ -296,7 +128,17 @@ abdefghi";
 
     #[test]
     fn test_example() {
-        assert_eq!(solvep1(EXAMPLE), 31);
-        assert_eq!(solvep2(EXAMPLE), 29);
+        let map = parse(EXAMPLE).unwrap();
+        assert_eq!(map.start, (0,0));
+        assert_eq!(map.end, (5,2));
+        assert_eq!(solvep1(&map), 31);
+        assert_eq!(solvep2(&map), 29);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("Sab\nEbE\n").err().unwrap();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.expected, "no second 'E'");
     }
 }
@@ -1,113 +1,176 @@
 
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
 use std::cmp::Ordering;
 
-type Num = u8;
+/// An integer of any width, kept as the digits it was written with
+#[derive(Clone,Debug)]
+pub struct Num(String);
+
+impl Num {
+    /// The digits without leading zeros, so equal values compare equal
+    fn significant(&self) -> &str {
+        let digits = self.0.trim_start_matches('0');
+        if digits.is_empty() { "0" } else { digits }
+    }
+}
+
+impl From<u64> for Num {
+    fn from(val: u64) -> Num {
+        Num(val.to_string())
+    }
+}
+
+impl Ord for Num {
+    fn cmp(&self, other: &Num) -> Ordering {
+        let (a, b) = (self.significant(), other.significant());
+        // More digits is bigger, otherwise digits compare like characters
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    }
+}
 
-type It<'a> = std::iter::Peekable<std::str::Bytes<'a>>;
+impl PartialOrd for Num {
+    fn partial_cmp(&self, other: &Num) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-#[derive(Clone,Debug,PartialEq)]
-enum Element {
+impl PartialEq for Num {
+    fn eq(&self, other: &Num) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Num { }
+
+impl std::fmt::Display for Num {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A packet, or part of one
+///
+/// Elements are ordered by the distress signal rules, so a packet is less
+/// than another if they're in the right order. Note this makes `[2]` equal
+/// to `[[2]]`.
+#[derive(Clone,Debug)]
+pub enum Element {
     Item(Num),
     List(Vec<Element>),
 }
 
+impl Element {
+    fn item(val: u64) -> Element {
+        Element::Item(Num::from(val))
+    }
 
-fn parse_number(iterator: &mut It) -> Num {
-    let mut num = 0;
-    loop {
-        match iterator.peek() {
-            Some(b']') | Some(b',') => {
-                return num;
-            },
-            Some(v) => {
-                if *v < b'0' || *v > b'9' {
-                    panic!("Unexpected character parsing number {}", v);
-                }
-                num = 10*num + (v - b'0');
-                iterator.next();
-            },
-            None => {
-                panic!("Unexpected end of line parsing number");
-            }
-        }
+    /// The packets that are added in part 2, `[[2]]` and `[[6]]`
+    fn divider(val: u64) -> Element {
+        Element::List(vec![Element::List(vec![Element::item(val)])])
     }
 }
 
-fn parse_list(iterator: &mut It) -> Vec<Element> {
-    assert_eq!(
-        iterator
-            .next()
-            .expect("Expected [ when parsing list"),
-        b'[',
-        "Expected [ when parsing list");
-    
-    let mut output = Vec::new();
-    loop {
-        match iterator.peek() {
-            Some(b']') => {
-                iterator.next();
-                return output;
-            }
-            Some(_) => {
-                output.push(parse_element(iterator));
-                if iterator.peek() == Some(&b',') {
-                    iterator.next();
+impl std::fmt::Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Element::Item(num) => write!(f, "{}", num),
+            Element::List(list) => {
+                write!(f, "[")?;
+                for (i, element) in list.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", element)?;
                 }
+                write!(f, "]")
             },
-            None => {
-                panic!("Unexpected end of line (expected element or ])");
-            }
         }
     }
 }
 
-fn parse_element(iterator: &mut It) -> Element {
-    match iterator.peek() {
-        Some(b'[') => {
-            Element::List(parse_list(iterator))
-        },
-        Some(v) if b'0' <= *v && *v <= b'9' => {
-            Element::Item(parse_number(iterator))
-        },
-        Some(v) => {
-            panic!("Unexpected byte {} (expected element)", v);
-        },
-        None => {
-            panic!("Unexpected end of line (expected element)");
-        }
+impl Ord for Element {
+    fn cmp(&self, other: &Element) -> Ordering {
+        packets_in_order(self, other)
     }
 }
 
-fn parse_line(line: &str) -> Element {
-    let mut it = line.bytes().peekable();
-    let output = parse_list(&mut it);
-    match it.next() {
-        Some(_) => {
-            panic!("Unexpected content at end of line");
-        },
-        None => { },
+impl PartialOrd for Element {
+    fn partial_cmp(&self, other: &Element) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Element {
+    fn eq(&self, other: &Element) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Element { }
+
+
+fn parse_num(src: &str, it: &mut ByteIterator) -> Result<Num, ParseError> {
+    let digits = take_while(src, it, is_digit);
+    if digits.is_empty() {
+        return Err(error_at(src, it, "a number or ["));
+    }
+    Ok(Num(digits.to_string()))
+}
+
+fn parse_list(src: &str, it: &mut ByteIterator) -> Result<Vec<Element>, ParseError> {
+    expect_sequence(src, it, "[")?;
+    if try_consume_sequence(src, it, "]")? {
+        return Ok(Vec::new());
+    }
+    let output = separated(src, it, ",", parse_element)?;
+    expect_sequence(src, it, "]")?;
+    Ok(output)
+}
+
+fn parse_element(src: &str, it: &mut ByteIterator) -> Result<Element, ParseError> {
+    match it.peek() {
+        Some((_, b'[')) => Ok(Element::List(parse_list(src, it)?)),
+        _ => Ok(Element::Item(parse_num(src, it)?)),
+    }
+}
+
+/// A packet is always a list
+fn parse_packet(src: &str, it: &mut ByteIterator) -> Result<Element, ParseError> {
+    Ok(Element::List(parse_list(src, it)?))
+}
+
+/// Pairs of packets, one per line, with a blank line between pairs
+fn parse(source: &str) -> Result<Vec<(Element, Element)>, ParseError> {
+    let mut it = get_byte_iterator(source);
+    let mut pairs = Vec::new();
+    loop {
+        // Consume blank lines
+        while try_consume_sequence(source, &mut it, "\n")? { }
+        if it.peek().is_none() {
+            return Ok(pairs);
+        }
+        let p1 = parse_packet(source, &mut it)?;
+        expect_sequence(source, &mut it, "\n")?;
+        let p2 = parse_packet(source, &mut it)?;
+        expect_line_end(source, &mut it)?;
+        pairs.push((p1, p2));
     }
-    Element::List(output)
 }
 
 fn packets_in_order(p1: &Element, p2: &Element) -> Ordering {
     match p1 {
         Element::Item(p1num) => match p2 {
             Element::Item(p2num) => {
-                if *p1num < *p2num  {
-                    return Ordering::Less;
-                } else if *p1num > *p2num {
-                    return Ordering::Greater;
-                } else {
-                    return Ordering::Equal;
-                }
+                p1num.cmp(p2num)
             },
             Element::List(p2list) => {
                 if p2list.len() == 0 {
                     return Ordering::Greater;
                 }
                 return packets_in_order(
-                    &Element::List(vec![Element::Item(*p1num)]),
+                    &Element::List(vec![Element::Item(p1num.clone())]),
                     &Element::List(p2list.to_vec()));
             }
         },
@@ -118,7 +181,7 @@ fn packets_in_order(p1: &Element, p2: &Element) -> Ordering {
                 }
                 return packets_in_order(
                     &Element::List(p1list.to_vec()),
-                    &Element::List(vec![Element::Item(*p2num)]));
+                    &Element::List(vec![Element::Item(p2num.clone())]));
             },
             Element::List(p2list) => {
                 for (p1val, p2val) in std::iter::zip(p1list, p2list) {
@@ -139,103 +202,52 @@ fn packets_in_order(p1: &Element, p2: &Element) -> Ordering {
     }
 }
 
-fn solve(input: &str) -> usize {
-    let mut src_it = input.split("\n");
-
-    let mut index = 1;
+fn solve(pairs: &[(Element, Element)]) -> usize {
     let mut output = 0;
-    loop {
-        let line1 = match src_it.next() {
-            Some(v) => v,
-            None => { break; }
-        };
-        if line1 == "" {
-            assert_eq!(src_it.next(), None);
-            break;
-        }
-        let p1 = parse_line(line1);
-        let p2 = parse_line(src_it.next().expect("Expected packet"));
-
-        if packets_in_order(&p1,&p2) != Ordering::Greater {
-            output += index;
-        }
-        index += 1;
-
-        match src_it.next() {
-            Some("") => { },
-            Some(_) => {
-                panic!("Expected empty line or EOF after pair of packets");
-            },
-            None => { break; }
+    for (i, (p1, p2)) in pairs.iter().enumerate() {
+        if p1 <= p2 {
+            output += i + 1;
         }
     }
     output
 }
 
-
-fn fold_func(acc: usize, (index, element): (usize, &Element)) -> usize {
-    match element {
-        Element::Item(_) => acc,
-        Element::List(l) => {
-            if l.len() != 1 {
-                acc
-            } else {
-                match &l[0] {
-                    Element::Item(_) => acc,
-                    Element::List(l2) => {
-                        if l2.len() != 1 {
-                            acc
-                        } else {
-                            if l2[0] == Element::Item(2) || l2[0] == Element::Item(6) {
-                                acc*(index+1)
-                            } else {
-                                acc
-                            }
-                        }
-                    }
-                }
-            }
-        }
+/// Product of where the divider packets end up once everything is sorted
+///
+/// Sorting isn't needed, a divider's index is one more than the number of
+/// packets that come before it.
+fn solvep2(pairs: &[(Element, Element)]) -> usize {
+    let dividers = [Element::divider(2), Element::divider(6)];
+    let mut output = 1;
+    for (i, divider) in dividers.iter().enumerate() {
+        let before = pairs.iter()
+            .map(|(p1, p2)| (p1 < divider) as usize + (p2 < divider) as usize)
+            .sum::<usize>();
+        // Earlier dividers come before this one as well
+        output *= before + i + 1;
     }
+    output
 }
 
-fn solvep2(input: &str) -> usize {
-    let mut src_it = input.split("\n");
+#[derive(Default)]
+pub struct Day13;
 
-    let mut packets = Vec::new();
-    loop {
-        let line1 = match src_it.next() {
-            Some(v) => v,
-            None => { break; }
-        };
-        if line1 == "" {
-            assert_eq!(src_it.next(), None);
-            break;
-        }
-        packets.push(parse_line(line1));
-        packets.push(parse_line(src_it.next().expect("Expected packet")));
+impl Solution for Day13 {
+    type Input = Vec<(Element, Element)>;
+    type Answer1 = usize;
+    type Answer2 = usize;
 
-        match src_it.next() {
-            Some("") => { },
-            Some(_) => {
-                panic!("Expected empty line or EOF after pair of packets");
-            },
-            None => { break; }
-        }
+    fn parse(&self, source: &str) -> Result<Vec<(Element, Element)>, ParseError> {
+        parse(source)
     }
-    packets.push(Element::List(vec![Element::List(vec![Element::Item(2)])]));
-    packets.push(Element::List(vec![Element::List(vec![Element::Item(6)])]));
-    packets.sort_by(packets_in_order);
 
-    packets.iter().enumerate().fold(1, fold_func)
-}
+    fn part1(&self, pairs: &Vec<(Element, Element)>) -> usize {
+        solve(pairs)
+    }
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d13.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input));
-    println!("{}", solvep2(&input));
+    fn part2(&self, pairs: &Vec<(Element, Element)>) -> usize {
+        solvep2(pairs)
+    }
 }
 
 
@@ -269,7 +281,77 @@ mod tests {
 
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE), 13);
-        assert_eq!(solvep2(EXAMPLE), 140);
+        let pairs = parse(EXAMPLE).unwrap();
+        assert_eq!(solve(&pairs), 13);
+        assert_eq!(solvep2(&pairs), 140);
+    }
+
+    fn parse_one(line: &str) -> Element {
+        let mut it = get_byte_iterator(line);
+        let packet = parse_packet(line, &mut it).unwrap();
+        expect_end(line, &mut it).unwrap();
+        packet
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for line in EXAMPLE.split("\n").filter(|l| !l.is_empty()) {
+            assert_eq!(parse_one(line).to_string(), line);
+        }
+    }
+
+    #[test]
+    fn test_wide_numbers() {
+        let big = "[18446744073709551616,[256,007]]";
+        assert_eq!(parse_one(big).to_string(), big);
+        assert!(parse_one("[256]") > parse_one("[255]"));
+        assert!(parse_one("[18446744073709551616]") > parse_one("[18446744073709551615]"));
+        assert_eq!(parse_one("[007]"), parse_one("[7]"));
+        assert_eq!(parse_one("[[2]]"), parse_one("[2]"));
+    }
+
+    /// Minimal xorshift so the property test is repeatable
+    fn next_rand(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    fn random_element(state: &mut u64, depth: usize) -> Element {
+        if depth == 0 || next_rand(state).is_multiple_of(3) {
+            Element::item(next_rand(state) % 12)
+        } else {
+            let len = (next_rand(state) % 4) as usize;
+            Element::List((0..len).map(|_| random_element(state, depth - 1)).collect())
+        }
+    }
+
+    #[test]
+    fn test_properties() {
+        let mut state = 0x2022_1213;
+        for _ in 0..500 {
+            let a = Element::List(vec![random_element(&mut state, 4)]);
+            let b = Element::List(vec![random_element(&mut state, 4)]);
+            let c = Element::List(vec![random_element(&mut state, 4)]);
+
+            // Display writes something parse reads back to the same packet
+            let text = a.to_string();
+            assert_eq!(parse_one(&text).to_string(), text);
+
+            // Ord is a total order
+            assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            assert_eq!(a.cmp(&a), Ordering::Equal);
+            if a <= b && b <= c {
+                assert!(a <= c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("[1,2]\n[1,,2]\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 4));
+        assert_eq!(err.expected, "a number or [");
     }
 }
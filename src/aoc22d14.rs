@@ -1,132 +1,27 @@
 
 
-use crate::arr2d::Array2d;
-
-use std::cmp::max;
+use crate::offset_grid::OffsetGrid;
+use crate::solution::Solution;
+use crate::parse_utils::*;
 
 type Coord = (isize, isize);
 
-type It<'a> = std::iter::Peekable<std::iter::Enumerate<std::str::Bytes<'a>>>;
-
-
-
-fn is_digit(byte: u8) -> bool {
-    b'0' <= byte && byte <= b'9'
+fn parse_coord(src: &str, it: &mut ByteIterator) -> Result<Coord, ParseError> {
+    let i = parse_signed(src, it)?;
+    expect_sequence(src, it, ",")?;
+    let j = parse_signed(src, it)?;
+    Ok((i,j))
 }
 
-
-fn parse_number(src: &str, it: &mut It) -> isize {
-    let start = match it.peek() {
-        Some((index, val)) => {
-            if is_digit(*val) {
-                *index
-            } else {
-                panic!("parse_number couldn't find the number");
-            }
-        },
-        None => {
-            panic!("parse_number couldn't find the number");
-        },
-    };
-    it.next();
-
-    let end = loop {
-        match it.peek() {
-            Some((index, val)) => {
-                if !is_digit(*val) {
-                    break *index;
-                }
-                it.next();
-            },
-            None => {
-                break usize::MAX;
-            }
-        }
-    };
-    
-    let substr = 
-        if end == usize::MAX {
-            &src[start..]
-        } else {
-            &src[start..end]
-        };
-
-    substr.parse().expect("Failed to parse number")
+/// Each line is a path of coordinates joined by arrows
+fn parse(source: &str) -> Result<Vec<Vec<Coord>>, ParseError> {
+    parse_lines(source, |src, it| separated(src, it, " -> ", parse_coord))
 }
 
 
-/// Consumes sequence from iterator it
-///
-/// If sequence is consumed, return true
-/// If first byte doesn't match, return false
-/// If other byte doesn't match, panic
-fn consume_sequence(it: &mut It, sequence: &str) -> bool {
-    let mut seq_it = sequence.bytes();
-    let seq_byte = seq_it.next().expect("sequence must be at least one character long");
-    match it.peek() {
-        Some((_, byte)) => {
-            if *byte != seq_byte {
-                return false;
-            }
-            it.next();
-        },
-        None => {
-            return false;
-        }
-    };
-
-    loop {
-        let seq_byte = match seq_it.next() {
-            Some(v) => v,
-            None => { return true; },
-        };
-        let it_byte = match it.next() {
-            Some((_, byte)) => byte,
-            None => {
-                panic!("Unexpected end of input consuming sequence \"{}\"", sequence);
-            }
-        };
-        if seq_byte != it_byte {
-            panic!("Unexpected byte {} consuming sequence \"{}\"", it_byte, sequence);
-        }
-    }
-}
-
-fn parse_coord(src: &str, it: &mut It) -> Coord {
-    let i = parse_number(src, it);
-    if !consume_sequence(it, ",") {
-        panic!("Missing ',' in coordinate");
-    }
-    let j = parse_number(src, it);
-    (i,j)
-}
-
-fn parse_line(line: &str) -> Vec<Coord> {
-    let mut output = Vec::new();
-    let mut it = line.bytes().enumerate().peekable();
-    output.push(parse_coord(&line, &mut it));
-
-    loop {
-        if !consume_sequence(&mut it, " -> ") {
-            return output;
-        }
-        output.push(parse_coord(&line, &mut it));
-    }
-}
-
-fn parse(source: &str) -> Vec<Vec<Coord>> {
-    let mut output = Vec::new();
-    for line in source.split("\n") {
-        let line = line.trim();
-        if line == "" { continue; }
-        output.push(parse_line(line));
-    }
-    output
-}
-
-
-fn max_coord((x0, y0): Coord, (x1, y1): &Coord) -> Coord {
-    (max(x0, *x1), max(y0, *y1))
+/// The lowest rock, nothing rests below this
+fn max_depth(lines: &[Vec<Coord>]) -> isize {
+    lines.iter().flatten().map(|(_, j)| *j).max().unwrap_or(0)
 }
 
 
@@ -139,7 +34,7 @@ fn get_direction(current: isize, target: isize) -> isize {
 /// Takes a world and fills in along a line
 ///
 /// A line is a series of coordinates
-fn apply_line(line: &Vec<Coord>, world: &mut Array2d<bool>) {
+fn apply_line(line: &[Coord], world: &mut OffsetGrid<bool>) {
     if line.len() == 0 {
         panic!("That ain't a line...");
     }
@@ -168,42 +63,35 @@ fn apply_line(line: &Vec<Coord>, world: &mut Array2d<bool>) {
 
 /// Drop a piece of sand from i,j into world. If lands, set location to occupied
 ///
-/// Returns true if landed, false if exited world
-fn drop_sand((mut i, mut j): Coord, world: &mut Array2d<bool>) -> bool {
-    let (sz_i, sz_j) = world.size_i();
-    loop {
-        let pj = j + 1;
-        // Down
-        let pi = i;
-        if pi < 0 || pi >= sz_i || pj < 0 || pj >= sz_j { return false; }
-        if !world.get((pi,pj)) { i = pi; j = pj; continue; }
-        // Down-left
-        let pi = i-1;
-        if pi < 0 || pi >= sz_i || pj < 0 || pj >= sz_j { return false; }
-        if !world.get((pi,pj)) { i = pi; j = pj; continue; }
-        // Down-right
-        let pi = i+1;
-        if pi < 0 || pi >= sz_i || pj < 0 || pj >= sz_j { return false; }
-        if !world.get((pi,pj)) { i = pi; j = pj; continue; }
+/// Returns true if landed, false if fell below max_depth into the abyss
+fn drop_sand((mut i, mut j): Coord, world: &mut OffsetGrid<bool>, max_depth: isize) -> bool {
+    'falling: loop {
+        if j >= max_depth {
+            return false;
+        }
+        // Down, down-left then down-right
+        for pi in [i, i-1, i+1] {
+            if !world.get((pi,j+1)) {
+                i = pi;
+                j += 1;
+                continue 'falling;
+            }
+        }
         // Cannot fall further
         *world.get_mut((i,j)) = true;
         return true;
     }
 }
 
-fn solve(input: &str) -> usize {
-    let lines = parse(input);
-
-    let (max_i, max_j) = lines.iter().fold((0,0), |acc, x| x.iter().fold(acc, max_coord));
-
-    let mut world = Array2d::new(false, max_i as usize + 1, max_j as usize + 1);
-    
-    for line in &lines {
+fn solve(lines: &Vec<Vec<Coord>>) -> usize {
+    let mut world = OffsetGrid::new(false);
+    for line in lines {
         apply_line(line, &mut world);
     }
+    let max_depth = max_depth(lines);
 
     let mut count = 0;
-    while drop_sand((500,0), &mut world) {
+    while drop_sand((500,0), &mut world, max_depth) {
         count += 1;
     }
     count
@@ -211,30 +99,24 @@ fn solve(input: &str) -> usize {
 
 /// Drop a piece of sand from i,j into world. If lands, set location to occupied
 ///
-/// Returns true if landed, false if exited world
-fn drop_sand_p2((mut i, mut j): Coord, world: &mut Array2d<bool>) -> bool {
-    let (sz_i, sz_j) = world.size_i();
-
+/// There is an infinite floor two below max_depth
+///
+/// Returns true if landed, false if the source is blocked
+fn drop_sand_p2((mut i, mut j): Coord, world: &mut OffsetGrid<bool>, max_depth: isize) -> bool {
     if *world.get((i,j)) {
         return false;
     }
 
-    loop {
-        let pj = j + 1;
-        // Down
-        let pi = i;
-        if !(pi < 0 || pi >= sz_i || pj < 0 || pj >= sz_j) {
-            if !world.get((pi,pj)) { i = pi; j = pj; continue; }
-        }
-        // Down-left
-        let pi = i-1;
-        if !(pi < 0 || pi >= sz_i || pj < 0 || pj >= sz_j) {
-            if !world.get((pi,pj)) { i = pi; j = pj; continue; }
-        }
-        // Down-right
-        let pi = i+1;
-        if !(pi < 0 || pi >= sz_i || pj < 0 || pj >= sz_j) {
-            if !world.get((pi,pj)) { i = pi; j = pj; continue; }
+    'falling: loop {
+        if j + 1 < max_depth + 2 {
+            // Down, down-left then down-right
+            for pi in [i, i-1, i+1] {
+                if !world.get((pi,j+1)) {
+                    i = pi;
+                    j += 1;
+                    continue 'falling;
+                }
+            }
         }
         // Cannot fall further
         *world.get_mut((i,j)) = true;
@@ -242,34 +124,39 @@ fn drop_sand_p2((mut i, mut j): Coord, world: &mut Array2d<bool>) -> bool {
     }
 }
 
-fn solve_p2(input: &str) -> usize {
-    let lines = parse(input);
-
-    let (max_i, max_j) = lines.iter().fold((0,0), |acc, x| x.iter().fold(acc, max_coord));
-
-    let mut world = Array2d::new(
-        false,
-        // Bit of a hack here, what if there are lines running to the left edge?
-        (max_i + 1 + max_j) as usize, // Make space to the right for sand piles
-        max_j as usize + 2); // Make space at the bottom before the floor
-    
-    for line in &lines {
+fn solve_p2(lines: &Vec<Vec<Coord>>) -> usize {
+    let mut world = OffsetGrid::new(false);
+    for line in lines {
         apply_line(line, &mut world);
     }
+    let max_depth = max_depth(lines);
 
     let mut count = 0;
-    while drop_sand_p2((500,0), &mut world) {
+    while drop_sand_p2((500,0), &mut world, max_depth) {
         count += 1;
     }
     count
 }
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d14.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input));
-    println!("{}", solve_p2(&input));
+#[derive(Default)]
+pub struct Day14;
+
+impl Solution for Day14 {
+    type Input = Vec<Vec<Coord>>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Vec<Vec<Coord>>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, lines: &Vec<Vec<Coord>>) -> usize {
+        solve(lines)
+    }
+
+    fn part2(&self, lines: &Vec<Vec<Coord>>) -> usize {
+        solve_p2(lines)
+    }
 }
 
 
@@ -282,11 +169,20 @@ mod tests {
 
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE), 24);
+        assert_eq!(solve(&parse(EXAMPLE).unwrap()), 24);
     }
 
     #[test]
     fn test_example_p2() {
-        assert_eq!(solve_p2(EXAMPLE), 93);
+        assert_eq!(solve_p2(&parse(EXAMPLE).unwrap()), 93);
+    }
+
+    #[test]
+    fn test_left_edge() {
+        // Rock running right up to (and past) x = 0, where a fixed size world
+        // would have ended
+        let lines = parse("0,5 -> 3,5\n-2,3 -> -2,4").unwrap();
+        assert_eq!(solve(&lines), 0);
+        assert_eq!(solve_p2(&lines), 49);
     }
 }
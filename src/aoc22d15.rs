@@ -1,11 +1,12 @@
 
 use crate::parse_utils::*;
 // use crate::arr2d::Array2d;
+use crate::solution::Solution;
 
 type Coord = (isize, isize);
 
 #[derive(Clone,Copy,Debug)]
-struct Sample {
+pub struct Sample {
     sensor: Coord,
     beacon: Coord,
 }
@@ -62,23 +63,18 @@ fn add_interval(start: isize, end: isize, set: &mut IntegerSet) {
 }
 
 
-fn parse(source: &str) -> Vec<Sample> {
-    let mut output = Vec::new();
-    for line in source.split("\n") {
-        if line == "" { continue; }
-        let mut it = get_byte_iterator(line);
-        assert!(consume_sequence(&mut it, "Sensor at x="));
-        let sx = parse_number(line, &mut it);
-        assert!(consume_sequence(&mut it, ", y="));
-        let sy = parse_number(line, &mut it);
-        assert!(consume_sequence(&mut it, ": closest beacon is at x="));
-        let bx = parse_number(line, &mut it);
-        assert!(consume_sequence(&mut it, ", y="));
-        let by = parse_number(line, &mut it);
-        assert_eq!(it.next(), None);
-        output.push(Sample { sensor: (sx,sy), beacon: (bx, by) });
-    }
-    output
+fn parse(source: &str) -> Result<Vec<Sample>, ParseError> {
+    parse_lines(source, |src, it| {
+        expect_sequence(src, it, "Sensor at x=")?;
+        let sx = parse_signed(src, it)?;
+        expect_sequence(src, it, ", y=")?;
+        let sy = parse_signed(src, it)?;
+        expect_sequence(src, it, ": closest beacon is at x=")?;
+        let bx = parse_signed(src, it)?;
+        expect_sequence(src, it, ", y=")?;
+        let by = parse_signed(src, it)?;
+        Ok(Sample { sensor: (sx,sy), beacon: (bx, by) })
+    })
 }
 
 fn add_sample(row: isize, sample: &Sample, set: &mut IntegerSet) {
@@ -95,16 +91,15 @@ fn add_sample(row: isize, sample: &Sample, set: &mut IntegerSet) {
     }
 }
 
-fn solve(input: &str, row: isize) -> isize {
-    let samples = parse(input);
+fn solve(samples: &Vec<Sample>, row: isize) -> isize {
     let mut blocked_values = Vec::new();
-    for sample in &samples {
+    for sample in samples {
         add_sample(row, sample, &mut blocked_values);
     }
     
     // Collect all the beacons locations
     let mut beacons = Vec::new();
-    for sample in &samples {
+    for sample in samples {
         let (bx,by) = &sample.beacon;
         if *by == row && !beacons.contains(bx) {
             beacons.push(*bx);
@@ -125,13 +120,12 @@ fn solve(input: &str, row: isize) -> isize {
 }
 
 
-fn solve_p2(input: &str, max_val: isize) -> isize {
-    let samples = parse(input);
+fn solve_p2(samples: &Vec<Sample>, max_val: isize) -> isize {
     let mut blocked_values = Vec::new();
     let mut output = None;
     for row in 0..max_val {
         blocked_values.clear();
-        for sample in &samples {
+        for sample in samples {
             add_sample(row, sample, &mut blocked_values);
         }
     
@@ -156,12 +150,45 @@ fn solve_p2(input: &str, max_val: isize) -> isize {
     }
 }
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d15.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input, 2000000));
-    println!("{}", solve_p2(&input, 4000000));
+pub struct Day15 {
+    /// Row to count excluded locations on
+    pub row: isize,
+    /// Distress beacon is within [0, max_val] in both directions
+    pub max_val: isize,
+}
+
+impl Default for Day15 {
+    fn default() -> Day15 {
+        Day15 {
+            row: 2000000,
+            max_val: 4000000,
+        }
+    }
+}
+
+impl Solution for Day15 {
+    type Input = Vec<Sample>;
+    type Answer1 = isize;
+    type Answer2 = isize;
+
+    fn example() -> Day15 {
+        Day15 {
+            row: 10,
+            max_val: 20,
+        }
+    }
+
+    fn parse(&self, source: &str) -> Result<Vec<Sample>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, samples: &Vec<Sample>) -> isize {
+        solve(samples, self.row)
+    }
+
+    fn part2(&self, samples: &Vec<Sample>) -> isize {
+        solve_p2(samples, self.max_val)
+    }
 }
 
 
@@ -186,11 +213,23 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3";
 
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE, 10), 26);
+        let day = Day15::example();
+        assert_eq!(day.part1(&day.parse(EXAMPLE).unwrap()), 26);
     }
 
     #[test]
     fn test_example_p2() {
-        assert_eq!(solve_p2(EXAMPLE, 20), 56000011);
+        let day = Day15::example();
+        assert_eq!(day.part2(&day.parse(EXAMPLE).unwrap()), 56000011);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let source = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+Sensor at x=9, y=1b";
+        let err = parse(source).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 19));
+        assert_eq!(err.expected, "\": closest beacon is at x=\"");
+        assert_eq!(err.found, "'b'");
     }
 }
@@ -1,6 +1,9 @@
 
 use crate::parse_utils::*;
 use crate::arr2d::Array2d;
+use crate::log;
+use crate::search;
+use crate::solution;
 
 type FlowRate = isize;
 type Time = isize;
@@ -13,81 +16,85 @@ struct RoomRaw {
 }
 
 #[derive(Debug)]
-struct ProblemRaw {
+pub struct ProblemRaw {
     total_flow_rate: FlowRate,
     aa_index: usize,
     rooms: Vec<RoomRaw>,
 }
 
 
-fn parse_room_code(it: &mut ByteIterator) -> String {
-    let (_, a) = it.next().expect("expected two characters for room code");
-    let (_, b) = it.next().expect("expected two characters for room code");
-    std::str::from_utf8(&[a,b]).expect("characters for room code are invalid").to_string()
+/// Room codes are two capital letters
+///
+/// Returns where the code was found, for errors, and the code itself
+fn parse_room_code(src: &str, it: &mut ByteIterator) -> Result<(usize, String), ParseError> {
+    let start = match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    };
+    let code = take_while(src, it, |b| b.is_ascii_uppercase());
+    if code.len() != 2 {
+        return Err(ParseError::new(src, start, "a two letter room code", code));
+    }
+    Ok((start, code.to_string()))
 }
 
-fn parse(source: &str) -> ProblemRaw {
-    let mut rooms = Vec::new();
+fn parse(source: &str) -> Result<ProblemRaw, ParseError> {
+    // Tunnels can lead to rooms we haven't seen yet, so keep the codes (and
+    // where they were for errors) until every room is known
+    let lines = parse_lines(source, |src, it| {
+        expect_sequence(src, it, "Valve ")?;
+        let room_code = parse_room_code(src, it)?;
+        expect_sequence(src, it, " has flow rate=")?;
+        let flow_rate = parse_signed(src, it)?;
+        expect_sequence(src, it, "; tunnel")?;
+        if !try_consume_sequence(src, it, "s lead to valves ")? {
+            expect_sequence(src, it, " leads to valve ")?;
+        }
+        let tunnels = separated(src, it, ", ", parse_room_code)?;
+        Ok((room_code, flow_rate, tunnels))
+    })?;
 
+    let mut rooms = Vec::new();
+    let mut tunnel_codes = Vec::new();
     let mut aa = None;
     let mut room_directory = std::collections::HashMap::new();
     let mut total_flow_rate = 0;
 
-    for line in source.split("\n") {
-        if line == "" { continue; }
-        let mut it = get_byte_iterator(line);
-        assert!(consume_sequence(&mut it, "Valve "));
-        let room_code = parse_room_code(&mut it);
-        assert!(consume_sequence(&mut it, " has flow rate="));
-        let flow_rate = parse_number(line, &mut it);
-
+    for ((code_offset, room_code), flow_rate, tunnels) in lines {
         if room_directory.insert(room_code.clone(), rooms.len()) != None {
-            panic!("Found two rooms with the code {}", room_code);
+            return Err(ParseError::new(source, code_offset, "a new room code", &room_code));
         }
         if room_code == "AA" {
             aa = Some(rooms.len());
         }
         total_flow_rate += flow_rate;
         rooms.push(RoomRaw { name: room_code, flow: flow_rate, tunnels: Vec::new() });
+        tunnel_codes.push(tunnels);
     }
-    
-    let mut index = 0;
-    for line in source.split("\n") {
-        if line == "" { continue; }
-        let room = &mut rooms[index];
-
-        let mut it = get_byte_iterator(line);
-        assert!(consume_sequence(&mut it, "Valve "));
-        parse_room_code(&mut it);
-        assert!(consume_sequence(&mut it, " has flow rate="));
-        parse_number(line, &mut it);
-        assert!(consume_sequence(&mut it, "; tunnel"));
-
-        if !consume_sequence(&mut it, "s lead to valves ") {
-            assert!(consume_sequence(&mut it, " leads to valve "));
-        }
-
-        loop {
-            let room_code = parse_room_code(&mut it);
-            (*room).tunnels.push(
-                match room_directory.get(&room_code) {
-                    Some(v) => *v,
-                    None => {
-                        panic!("Unknown room code: '{}'", room_code);
-                    }
-                });
-            if !consume_sequence(&mut it, ", ") {
-                break;
+
+    for (room, tunnels) in std::iter::zip(&mut rooms, tunnel_codes) {
+        for (offset, room_code) in tunnels {
+            match room_directory.get(&room_code) {
+                Some(v) => { room.tunnels.push(*v); },
+                None => {
+                    return Err(ParseError::new(source, offset, "a known room code", &room_code));
+                }
             }
         }
-        index += 1;
     }
 
-    ProblemRaw {
+    let aa_index = match aa {
+        Some(v) => v,
+        None => {
+            return Err(ParseError::new(source, source.len(), "room 'AA'", "end of input"));
+        }
+    };
+
+    Ok(ProblemRaw {
         total_flow_rate: total_flow_rate,
-        aa_index: aa.expect("Failed to find room 'AA'"),
+        aa_index,
         rooms: rooms,
-    }
+    })
 }
 
 
@@ -98,6 +105,8 @@ struct Problem {
     rooms: Vec<FlowRate>,
     room_names: Vec<String>,
     adjacency: Array2d<Time>,
+    /// Shortest time between any two rooms with valves worth opening
+    min_travel: Time,
 }
 
 /// Remaps rooms to only have the initial room and non zero rooms
@@ -128,41 +137,15 @@ fn remap_rooms(raw: &ProblemRaw) -> (usize, Vec<usize>, Vec<FlowRate>, Vec<Strin
 
 /// Finds the shortest paths from index to all other nodes in the RAW problem
 fn shortest_paths(index: usize, raw: &ProblemRaw) -> Vec<Time> {
+    let result = search::bfs([index], |current| raw.rooms[current].tunnels.iter().copied(), |_| false);
 
     // output is the amount of time taken to go from here to there
-    let mut output = Vec::with_capacity(raw.rooms.len());
-    for i in 0..raw.rooms.len() {
-        if i == index {
-            output.push(0);
-        } else {
-            output.push(Time::MAX);
-        }
-    }
-
-    // Use BFS to search the graph
-    //
-    // Basically, just look at neighbours and see if going from here to there
-    // improves their score, and if so, try jumping on again
-    let mut queue = std::collections::VecDeque::new();
-    queue.push_back(index);
-    loop {
-        match queue.pop_front() {
-            Some(current) => {
-                let current_time = output[current];
-
-                for n in raw.rooms[current].tunnels.clone() {
-                    let n_time = output[n];
-                    if current_time + 1 < n_time {
-                        output[n] = current_time + 1;
-                        queue.push_back(n);
-                    }
-                }
-            },
-            None => { break; },
-        }
-    }
-
-    output
+    (0..raw.rooms.len())
+        .map(|i| match result.distance(i) {
+            Some(d) => d as Time,
+            None => Time::MAX,
+        })
+        .collect()
 }
 
 /// Reduce down the problem, many rooms have zero flow and so lead to walking
@@ -186,27 +169,47 @@ fn reduce_problem(raw: &ProblemRaw, num_rounds: Time) -> Problem {
         }
     }
 
-    println!("New adjacency:");
-    for i in 0..new_rooms.len() {
-        print!("  ");
-        for j in 0..new_rooms.len() {
-            print!("{:3} ", adj.getu((i,j)));
-        }
-        print!("\n");
+    if new_rooms.len() > ValveSet::BITS as usize {
+        panic!("Too many valves to track in a ValveSet");
     }
 
-    Problem {
+    let mut problem = Problem {
         num_rounds: num_rounds,
         total_flow_rate: raw.total_flow_rate,
         aa_index: new_aa_index,
         rooms: new_rooms,
         room_names: new_room_names,
         adjacency: adj,
+        min_travel: 0,
+    };
+    problem.min_travel = min_travel_time(&problem);
+    log::debug!("New adjacency:\n{}", format_adjacency(&problem));
+    problem
+}
+
+/// Travel times between the reduced rooms as a table, '-' where there's no way
+fn format_adjacency(problem: &Problem) -> String {
+    let mut output = String::from("  ");
+    for name in problem.room_names.iter() {
+        output += &format!(" {:>3}", name);
+    }
+    for (i, name) in problem.room_names.iter().enumerate() {
+        output += &format!("\n{}", name);
+        for j in 0..problem.rooms.len() {
+            match *problem.adjacency.getu((i, j)) {
+                Time::MAX => { output += "   -"; },
+                time => { output += &format!(" {:3}", time); },
+            }
+        }
     }
+    output
 }
 
 
-#[derive(Clone,Copy,Debug)]
+/// Set of valves as a bitmask of reduced room indices
+type ValveSet = u64;
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,PartialOrd,Ord)]
 struct Agent {
     target_room: usize,
     target_room_rate: FlowRate,
@@ -240,162 +243,701 @@ impl<const N_AGENTS: usize> Solution<N_AGENTS> {
     }
 }
 
-fn valve_is_open<const N_AGENTS: usize>(
-        valve: usize,
-        open_valves_stacks: &mut [Vec<usize>; N_AGENTS])
-    -> bool
-{
-    for stack in open_valves_stacks {
-        if stack.contains(&valve) { return true; }
+/// What's left to decide once an agent arrives, the agents are sorted as
+/// which is which doesn't matter
+type MemoKey<const N_AGENTS: usize> = (ValveSet, [Agent; N_AGENTS], Time);
+
+/// Rooms agents head for, in the order the search decides them, IDLE for an
+/// agent that stops. Held last decision first, as they're built up from the
+/// end.
+type Decisions = Vec<usize>;
+
+/// Target room of an agent that's stopped for good
+const IDLE: usize = usize::MAX;
+
+/// Search state shared by the whole depth first search
+struct Search<const N_AGENTS: usize> {
+    /// Most pressure released by any complete plan found so far
+    best: FlowRate,
+    /// Exact pressure released from a state's current time to the end, and
+    /// the decisions that release it
+    memo: std::collections::HashMap<MemoKey<N_AGENTS>, (FlowRate, Decisions)>,
+}
+
+/// Which agent decides where to go next: the first to arrive
+///
+/// Ties are broken by room rather than position, so the same agents in
+/// another order make the same decisions, which memoised decisions rely on.
+fn next_agent<const N_AGENTS: usize>(agents: &[Agent; N_AGENTS]) -> usize {
+    (0..N_AGENTS).min_by_key(|i| (agents[*i].arrival_time, agents[*i].target_room)).unwrap()
+}
+
+/// Shortest time between any two distinct rooms worth visiting
+fn min_travel_time(problem: &Problem) -> Time {
+    let mut output = Time::MAX;
+    for i in 0..problem.rooms.len() {
+        for j in 0..problem.rooms.len() {
+            if i != j && problem.rooms[j] > 0 {
+                output = std::cmp::min(output, *problem.adjacency.getu((i, j)));
+            }
+        }
     }
-    false
+    output
 }
 
-/// Performs a depth first search on the tunnel system. Returns the pressure released
-fn depth_first_search<const N_AGENTS: usize>(
-        mut best: FlowRate,
-        solution: Solution<N_AGENTS>,
-        open_valves_stacks: &mut [Vec<usize>; N_AGENTS],
+/// Most pressure that could possibly be released from the current time on
+///
+/// Agent agent_i has just arrived, so its valve already counts towards the
+/// current rate. Other agents' valves count from when they arrive. The rest
+/// is the smaller of two optimistic guesses for the closed valves:
+///
+///  - Each valve opened by whichever agent could get there first, as if no
+///    other valve took any time
+///  - Biggest valves opened first, as early as any agent could open another
+///    valve, moving between valves in the shortest time there is
+///
+fn upper_bound<const N_AGENTS: usize>(
+        solution: &Solution<N_AGENTS>,
+        agent_i: usize,
+        open_valves: ValveSet,
         problem: &Problem)
     -> FlowRate
 {
-    // Which agent is first?
-    let agent_i = {
-        let mut best_agent = 0;
-        let mut best_time = Time::MAX;
-        for (i, agent) in solution.agents.iter().enumerate() {
-            if agent.arrival_time < best_time {
-                best_agent = i;
-                best_time = agent.arrival_time;
+    let end = problem.num_rounds;
+    let mut output = solution.current_rate*(end - solution.current_time);
+
+    let mut closed: Vec<usize> = (0..problem.rooms.len())
+        .filter(|r| open_valves & (1 << r) == 0)
+        .collect();
+    closed.sort_by_key(|r| -problem.rooms[*r]);
+
+    let mut earliest_total = 0;
+    let mut slots = Vec::new();
+    let mut active = Vec::new();
+    for (i, agent) in solution.agents.iter().enumerate() {
+        if agent.arrival_time >= end {
+            continue;
+        }
+        if i != agent_i {
+            output += agent.target_room_rate*(end - agent.arrival_time);
+        }
+        active.push(agent);
+        // Unreachable valves are Time::MAX away, and never get opened
+        let nearest = closed.iter()
+            .map(|r| *problem.adjacency.getu((agent.target_room, *r)))
+            .filter(|t| *t != Time::MAX)
+            .min();
+        if let Some(nearest) = nearest {
+            let mut t = agent.arrival_time + nearest + 1;
+            while t < end {
+                slots.push(t);
+                t = t.saturating_add(problem.min_travel).saturating_add(1);
             }
         }
-        best_agent
-    };
+    }
+    slots.sort();
+
+    for r in closed.iter() {
+        let earliest = active.iter()
+            .filter_map(|a| match *problem.adjacency.getu((a.target_room, *r)) {
+                Time::MAX => None,
+                travel => Some(a.arrival_time + travel + 1),
+            })
+            .min();
+        if let Some(t) = earliest {
+            if t < end {
+                earliest_total += problem.rooms[*r]*(end - t);
+            }
+        }
+    }
 
-    let agent = &solution.agents[agent_i];
+    let mut greedy_total = 0;
+    for (r, t) in std::iter::zip(closed, slots) {
+        greedy_total += problem.rooms[r]*(end - t);
+    }
+    output + std::cmp::min(earliest_total, greedy_total)
+}
+
+/// Performs a depth first search on the tunnel system
+///
+/// Returns the pressure released from solution's current time to the end
+/// by the best plan found, the decisions making it up, and whether it's the
+/// best possible. It may not be if branches were cut because they couldn't
+/// beat search.best, in which case it's only a lower bound and isn't
+/// memoised.
+fn depth_first_search<const N_AGENTS: usize>(
+        solution: Solution<N_AGENTS>,
+        open_valves: ValveSet,
+        problem: &Problem,
+        search: &mut Search<N_AGENTS>)
+    -> (FlowRate, Decisions, bool)
+{
+    let agent_i = next_agent(&solution.agents);
+    let agent = solution.agents[agent_i];
     
     // If we run out of rounds, then stop
     if agent.arrival_time >= problem.num_rounds {
         let dt = problem.num_rounds - solution.current_time;
-        let pressure_released = solution.pressure_released + dt*solution.current_rate;
-        return std::cmp::max(pressure_released, best);
+        search.best = std::cmp::max(search.best, solution.pressure_released + dt*solution.current_rate);
+        return (dt*solution.current_rate, Vec::new(), true);
     }
 
     // Advance time
     let dt = agent.arrival_time - solution.current_time;
+    let gained = dt*solution.current_rate;
     let solution = Solution {
         agents: solution.agents, // Just copy the agents
         current_time: agent.arrival_time,
         current_rate: solution.current_rate + agent.target_room_rate,
-        pressure_released: solution.pressure_released + dt*solution.current_rate,
+        pressure_released: solution.pressure_released + gained,
     };
 
-    let remaining = problem.num_rounds - agent.arrival_time;
-    // If we can't do better than the best, then stop
-    if solution.pressure_released + remaining*problem.total_flow_rate < best {
-        //return best;
-    }
-
     // If we run out of valves to turn, stop
+    let remaining = problem.num_rounds - solution.current_time;
     if solution.current_rate == problem.total_flow_rate {
-        let total_pressure  = solution.pressure_released + remaining*solution.current_rate;
-        return std::cmp::max(total_pressure, best);
+        let future = remaining*solution.current_rate;
+        search.best = std::cmp::max(search.best, solution.pressure_released + future);
+        return (gained + future, Vec::new(), true);
     }
 
-    // What if we travelled and opened a valve?
-    for new_room in 0..problem.rooms.len() {
-        if valve_is_open(new_room, open_valves_stacks) {
-            // Can't open already-openned valves
-            continue;
+    let mut sorted_agents = solution.agents;
+    sorted_agents.sort();
+    let key = (open_valves, sorted_agents, solution.current_time);
+    if let Some((future, decisions)) = search.memo.get(&key) {
+        search.best = std::cmp::max(search.best, solution.pressure_released + future);
+        return (gained + future, decisions.clone(), true);
+    }
+
+    // If we can't do better than the best, then stop
+    let bound = upper_bound(&solution, agent_i, open_valves, problem);
+    if solution.pressure_released + bound <= search.best {
+        return (gained, Vec::new(), false);
+    }
+
+    let mut best_future = 0;
+    let mut best_decisions = Vec::new();
+    let mut exact = true;
+    let mut try_next = |agent: Agent, open_valves: ValveSet, search: &mut Search<N_AGENTS>| {
+        let mut new_agents = solution.agents;
+        new_agents[agent_i] = agent;
+        let new_solution = 
+            Solution {
+                agents: new_agents,
+                ..solution
+            };
+        let (future, mut decisions, child_exact) =
+            depth_first_search(new_solution, open_valves, problem, search);
+        if future > best_future {
+            best_future = future;
+            decisions.push(agent.target_room);
+            best_decisions = decisions;
         }
+        exact &= child_exact;
+    };
 
+    // What if we travelled and opened a valve? Biggest valves first, to find
+    // good plans early and cut more
+    let mut candidates: Vec<usize> = (0..problem.rooms.len())
+        .filter(|r| open_valves & (1 << r) == 0)
+        .collect();
+    candidates.sort_by_key(|r| -problem.rooms[*r]);
+    for new_room in candidates {
         let move_time = problem.adjacency.getu((agent.target_room, new_room));
-        if solution.current_time + move_time + 1 >= problem.num_rounds {
-            // We don't have time to go to this valve
+        if *move_time == Time::MAX || solution.current_time + move_time + 1 >= problem.num_rounds {
+            // We can't get to this valve, or don't have time to
             continue;
         }
 
-        let mut new_agents = solution.agents;
-        new_agents[agent_i] =
+        let new_agent =
             Agent {
                 target_room: new_room,
                 target_room_rate: problem.rooms[new_room],
                 arrival_time: agent.arrival_time + move_time + 1,
             };
-
-        let new_solution = 
-            Solution {
-                agents: new_agents,
-                ..solution
-            };
-
-        open_valves_stacks[agent_i].push(new_room);
-        best = std::cmp::max(
-            best,
-            depth_first_search(
-                best,
-                new_solution,
-                open_valves_stacks,
-                problem));
-        assert_eq!(open_valves_stacks[agent_i].pop(), Some(new_room));
+        try_next(new_agent, open_valves | 1 << new_room, search);
     }
     
     // What if we stayed here and did nothing?
-    let mut new_agents = solution.agents;
-    new_agents[agent_i] =
+    let idle =
         Agent {
-            target_room: usize::MAX,
+            target_room: IDLE,
             target_room_rate: 0,
             arrival_time: Time::MAX,
         };
+    try_next(idle, open_valves, search);
 
-    let new_solution = 
-        Solution {
-            agents: new_agents,
-            ..solution
+    if exact {
+        search.memo.insert(key, (best_future, best_decisions.clone()));
+    }
+    (gained + best_future, best_decisions, exact)
+}
+
+
+/// Best plan for N_AGENTS, searching through their moves together
+fn plan_search<const N_AGENTS: usize>(problem_raw: &ProblemRaw, num_rounds: isize) -> Plan {
+    let problem = reduce_problem(problem_raw, num_rounds);
+
+    // Valves with no flow may as well be open already
+    let open_valves = (0..problem.rooms.len())
+        .filter(|r| problem.rooms[*r] == 0)
+        .fold(0, |set, r| set | 1 << r);
+
+    let mut search = Search {
+        best: 0,
+        memo: std::collections::HashMap::new(),
+    };
+    let start = Solution::<N_AGENTS>::new(problem.aa_index);
+    let (_, mut decisions, _) = depth_first_search(start, open_valves, &problem, &mut search);
+    decisions.reverse();
+
+    // Replay the decisions to find which agent made each
+    let mut agents = start.agents;
+    let mut routes = vec![Vec::new(); N_AGENTS];
+    for room in decisions {
+        let i = next_agent(&agents);
+        if room == IDLE {
+            agents[i] = Agent { target_room: IDLE, target_room_rate: 0, arrival_time: Time::MAX };
+            continue;
+        }
+        let travel = problem.adjacency.getu((agents[i].target_room, room));
+        agents[i] = Agent {
+            target_room: room,
+            target_room_rate: problem.rooms[room],
+            arrival_time: agents[i].arrival_time + travel + 1,
         };
+        routes[i].push(room);
+    }
+
+    let plan = make_plan(problem_raw, &problem, routes);
+    assert_eq!(plan.total, search.best, "Search's plan doesn't release what it found");
+    plan
+}
+
 
-    best = std::cmp::max(
-        best,
-        depth_first_search(
-            best,
-            new_solution,
-            open_valves_stacks,
-            problem));
+/// Best pressure a single agent can release opening exactly each set of valves
+///
+/// Valves with flow are numbered densely, bit i of the index is valves[i].
+/// Sets that can't be opened in time release nothing.
+fn best_per_subset(problem: &Problem, valves: &[usize]) -> Vec<FlowRate> {
+    fn visit(
+            room: usize,
+            time: Time,
+            opened: usize,
+            pressure: FlowRate,
+            problem: &Problem,
+            valves: &[usize],
+            best: &mut Vec<FlowRate>)
+    {
+        best[opened] = std::cmp::max(best[opened], pressure);
+        for (i, valve) in valves.iter().enumerate() {
+            let travel = *problem.adjacency.getu((room, *valve));
+            if opened & (1 << i) != 0 || travel == Time::MAX {
+                continue;
+            }
+            let open_time = time + travel + 1;
+            if open_time >= problem.num_rounds {
+                continue;
+            }
+            let released = problem.rooms[*valve]*(problem.num_rounds - open_time);
+            visit(*valve, open_time, opened | 1 << i, pressure + released, problem, valves, best);
+        }
+    }
 
+    let mut best = vec![0; 1 << valves.len()];
+    visit(problem.aa_index, 0, 0, 0, problem, valves, &mut best);
     best
 }
 
+/// Best order to open a set of valves in, for a single agent
+fn best_route(problem: &Problem, valves: &[usize], set: usize) -> Vec<usize> {
+    fn visit(
+            room: usize,
+            time: Time,
+            pressure: FlowRate,
+            route: &mut Vec<usize>,
+            problem: &Problem,
+            valves: &[usize],
+            best: &mut (FlowRate, Vec<usize>))
+    {
+        if pressure > best.0 {
+            *best = (pressure, route.clone());
+        }
+        for valve in valves.iter() {
+            let travel = *problem.adjacency.getu((room, *valve));
+            if route.contains(valve) || travel == Time::MAX {
+                continue;
+            }
+            let open_time = time + travel + 1;
+            if open_time >= problem.num_rounds {
+                continue;
+            }
+            let released = problem.rooms[*valve]*(problem.num_rounds - open_time);
+            route.push(*valve);
+            visit(*valve, open_time, pressure + released, route, problem, valves, best);
+            route.pop();
+        }
+    }
 
-fn solve<const N_AGENTS: usize>(input: &str, num_rounds: isize)
-    -> FlowRate
-    where [Vec<usize>; N_AGENTS]: Default
-{
-    let problem_raw = parse(input);
-    let problem = reduce_problem(&problem_raw, num_rounds);
+    let in_set: Vec<usize> = valves.iter().enumerate()
+        .filter(|(i, _)| set & (1 << i) != 0)
+        .map(|(_, valve)| *valve)
+        .collect();
+    let mut best = (0, Vec::new());
+    visit(problem.aa_index, 0, 0, &mut Vec::new(), problem, &in_set, &mut best);
+    best.1
+}
 
-    let mut room_stacks = Default::default();
-    
-    let output = depth_first_search::<N_AGENTS>(
-        0,
-        Solution::new(problem.aa_index),
-        &mut room_stacks,
-        &problem);
-    
-    for room_stack in room_stacks {
-        assert_eq!(room_stack.len(), 0);
+/// A valve being opened as part of a plan
+#[derive(Clone,Debug,PartialEq)]
+struct Opening {
+    /// Room codes walked through to get to the valve, ending at it
+    path: Vec<String>,
+    /// Minute the valve is opened in, it releases pressure after that
+    minute: Time,
+    /// Pressure the valve releases by the end
+    pressure: FlowRate,
+}
+
+/// Who opens which valves when
+#[derive(Clone,Debug,PartialEq)]
+struct Plan {
+    start: String,
+    /// Valves each agent opens, in order
+    agents: Vec<Vec<Opening>>,
+    total: FlowRate,
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, openings) in self.agents.iter().enumerate() {
+            writeln!(f, "Agent {}:", i + 1)?;
+            if openings.is_empty() {
+                writeln!(f, "  Nothing worth doing")?;
+            }
+            let mut from = &self.start;
+            for opening in openings {
+                writeln!(
+                    f,
+                    "  Minute {:2}: open {}, releasing {} ({} -> {})",
+                    opening.minute,
+                    opening.path.last().unwrap(),
+                    opening.pressure,
+                    from,
+                    opening.path.join(" -> "))?;
+                from = opening.path.last().unwrap();
+            }
+        }
+        write!(f, "Total pressure released: {}", self.total)
+    }
+}
+
+/// Turns routes through the reduced problem into a plan with room codes
+fn make_plan(raw: &ProblemRaw, problem: &Problem, routes: Vec<Vec<usize>>) -> Plan {
+    let raw_index = |room: usize| {
+        raw.rooms.iter().position(|r| r.name == problem.room_names[room]).unwrap()
+    };
+
+    let mut agents = Vec::new();
+    let mut total = 0;
+    for route in routes {
+        let mut openings = Vec::new();
+        let mut room = problem.aa_index;
+        let mut time = 0;
+        for valve in route {
+            time += problem.adjacency.getu((room, valve)) + 1;
+            let pressure = problem.rooms[valve]*(problem.num_rounds - time);
+            let walk = search::bfs(
+                [raw_index(room)],
+                |current| raw.rooms[current].tunnels.iter().copied(),
+                |current| current == raw_index(valve));
+            let path = walk.path_to(raw_index(valve)).unwrap();
+            openings.push(Opening {
+                path: path[1..].iter().map(|r| raw.rooms[*r].name.clone()).collect(),
+                minute: time,
+                pressure,
+            });
+            total += pressure;
+            room = valve;
+        }
+        agents.push(openings);
+    }
+
+    Plan {
+        start: problem.room_names[problem.aa_index].clone(),
+        agents,
+        total,
+    }
+}
+
+/// Best plan for n_agents, worked out per set of valves
+///
+/// Each agent opens a different set of valves, so the best for n agents
+/// with a set of valves to share is the best split of it between one agent
+/// and the other n - 1. That's 3^valves work per extra agent, but doesn't
+/// care how the agents' moves interleave.
+fn plan_subsets(problem_raw: &ProblemRaw, num_rounds: isize, n_agents: usize) -> Plan {
+    let problem = reduce_problem(problem_raw, num_rounds);
+    let valves: Vec<usize> = (0..problem.rooms.len())
+        .filter(|r| problem.rooms[*r] > 0)
+        .collect();
+    if valves.len() > 24 {
+        panic!("Too many valves to go through every subset of them");
+    }
+
+    // Best for one agent using at most the valves in each set, along with
+    // the set it actually opens
+    let mut single = best_per_subset(&problem, &valves);
+    let mut opened: Vec<usize> = (0..single.len()).collect();
+    for i in 0..valves.len() {
+        for set in 0..single.len() {
+            if set & (1 << i) != 0 && single[set ^ 1 << i] > single[set] {
+                single[set] = single[set ^ 1 << i];
+                opened[set] = opened[set ^ 1 << i];
+            }
+        }
+    }
+
+    // splits[k][set] is what agent k + 1 gets when sharing set with those
+    // before it
+    let all = single.len() - 1;
+    let mut team = single.clone();
+    let mut splits = Vec::new();
+    for _ in 1..n_agents {
+        let mut next = vec![0; single.len()];
+        let mut split = vec![0; single.len()];
+        for set in 0..single.len() {
+            // Every way of giving part of set to the new agent
+            let mut part = set;
+            loop {
+                let pressure = single[part] + team[set ^ part];
+                if pressure > next[set] {
+                    next[set] = pressure;
+                    split[set] = part;
+                }
+                if part == 0 {
+                    break;
+                }
+                part = (part - 1) & set;
+            }
+        }
+        team = next;
+        splits.push(split);
+    }
+
+    // Work back through the splits to who opens what
+    let mut sets = Vec::new();
+    let mut remaining = all;
+    for split in splits.iter().rev() {
+        sets.push(opened[split[remaining]]);
+        remaining ^= split[remaining];
+    }
+    if n_agents > 0 {
+        sets.push(opened[remaining]);
+    }
+    sets.reverse();
+
+    let routes = sets.into_iter().map(|set| best_route(&problem, &valves, set)).collect();
+    make_plan(problem_raw, &problem, routes)
+}
+
+
+/// Colours for each agent's route when highlighting a plan
+const ROUTE_COLOURS: [&str; 5] = ["red", "blue", "darkgreen", "orange", "purple"];
+
+/// An agent moving between two rooms, by name
+type Move = ((String, String), usize);
+
+/// Pairs of rooms each agent moves between and the valves it opens, by name
+fn plan_steps(plan: &Plan, tunnel_by_tunnel: bool) -> (Vec<Move>, Vec<(String, String)>) {
+    let mut moves = Vec::new();
+    let mut valves = Vec::new();
+    for (i, openings) in plan.agents.iter().enumerate() {
+        let colour = ROUTE_COLOURS[i % ROUTE_COLOURS.len()];
+        let mut from = plan.start.clone();
+        for opening in openings {
+            let valve = opening.path.last().unwrap().clone();
+            if tunnel_by_tunnel {
+                for room in opening.path.iter() {
+                    moves.push(((from, room.clone()), i));
+                    from = room.clone();
+                }
+            } else {
+                moves.push(((from, valve.clone()), i));
+            }
+            valves.push((valve.clone(), format!(
+                "fillcolor={}, style=filled, xlabel=\"minute {}\"", colour, opening.minute)));
+            from = valve;
+        }
     }
+    (moves, valves)
+}
 
+/// Writes an undirected graph with rooms labelled by flow rate
+///
+/// edges are (a, b, travel time), each pair once. If there's a plan its
+/// route is drawn in a colour per agent and the valves opened are filled in.
+fn write_dot(
+        rooms: &Vec<(String, FlowRate)>,
+        edges: &Vec<(usize, usize, Time)>,
+        plan: Option<&Plan>,
+        tunnel_by_tunnel: bool)
+    -> String
+{
+    let (moves, valves) = match plan {
+        Some(plan) => plan_steps(plan, tunnel_by_tunnel),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let mut output = String::from("graph tunnels {\n");
+    for (name, flow) in rooms {
+        let mut attributes = format!("label=\"{}\\nflow {}\"", name, flow);
+        for (valve, extra) in valves.iter() {
+            if valve == name {
+                attributes += ", ";
+                attributes += extra;
+            }
+        }
+        output += &format!("    {} [{}];\n", name, attributes);
+    }
+    for (a, b, time) in edges {
+        let (a, b) = (&rooms[*a].0, &rooms[*b].0);
+        let mut attributes = format!("label=\"{}\", len={}", time, time);
+        let used: Vec<usize> = moves.iter()
+            .filter(|((from, to), _)| (from == a && to == b) || (from == b && to == a))
+            .map(|(_, agent)| *agent)
+            .collect();
+        if !used.is_empty() {
+            let colours: Vec<&str> = used.iter()
+                .map(|agent| ROUTE_COLOURS[agent % ROUTE_COLOURS.len()])
+                .collect();
+            attributes += &format!(", color=\"{}\", penwidth=3", colours.join(":"));
+        }
+        output += &format!("    {} -- {} [{}];\n", a, b, attributes);
+    }
+    output += "}\n";
     output
 }
 
+/// The tunnels as given, in Graphviz DOT
+fn raw_to_dot(raw: &ProblemRaw, plan: Option<&Plan>) -> String {
+    let rooms = raw.rooms.iter().map(|r| (r.name.clone(), r.flow)).collect();
+    let mut edges = Vec::new();
+    for (a, room) in raw.rooms.iter().enumerate() {
+        for b in room.tunnels.iter() {
+            // Tunnels are listed from both ends
+            let (a, b) = (std::cmp::min(a, *b), std::cmp::max(a, *b));
+            if !edges.contains(&(a, b, 1)) {
+                edges.push((a, b, 1));
+            }
+        }
+    }
+    write_dot(&rooms, &edges, plan, true)
+}
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d16.txt")
-        .expect("Failed to read input");
-    println!("{}", solve::<1>(&input, 30));
-    println!("{}", solve::<2>(&input, 26)); // 2514 is too low
+/// The rooms worth visiting and the time between each pair, in Graphviz DOT
+fn reduced_to_dot(problem: &Problem, plan: Option<&Plan>) -> String {
+    let rooms = std::iter::zip(&problem.room_names, &problem.rooms)
+        .map(|(name, flow)| (name.clone(), *flow))
+        .collect();
+    let mut edges = Vec::new();
+    for a in 0..problem.rooms.len() {
+        for b in (a+1)..problem.rooms.len() {
+            let time = *problem.adjacency.getu((a, b));
+            if time != Time::MAX {
+                edges.push((a, b, time));
+            }
+        }
+    }
+    write_dot(&rooms, &edges, plan, false)
+}
+
+/// Writes both graphs for a part to dir, highlighting the plan if given
+fn export_dot(
+        dir: &std::path::Path,
+        part: usize,
+        raw: &ProblemRaw,
+        num_rounds: Time,
+        plan: Option<&Plan>)
+{
+    let problem = reduce_problem(raw, num_rounds);
+    let outputs = [
+        (format!("d16_raw_p{}.dot", part), raw_to_dot(raw, plan)),
+        (format!("d16_reduced_p{}.dot", part), reduced_to_dot(&problem, plan)),
+    ];
+    for (name, dot) in outputs {
+        let path = dir.join(name);
+        if let Err(e) = std::fs::write(&path, dot) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+
+pub struct Day16 {
+    /// Minutes available when working alone
+    pub rounds_p1: Time,
+    /// Minutes available after teaching the elephant
+    pub rounds_p2: Time,
+    /// How many of us open valves in part 2, counting the elephant
+    pub agents_p2: usize,
+    /// Print the plan behind each answer to stderr, and highlight it in
+    /// any DOT written
+    pub explain: bool,
+    /// Write the tunnels as Graphviz DOT here for each part
+    pub dot_dir: Option<std::path::PathBuf>,
+}
+
+impl Default for Day16 {
+    fn default() -> Day16 {
+        Day16 {
+            rounds_p1: 30,
+            rounds_p2: 26,
+            agents_p2: 2,
+            explain: false,
+            dot_dir: None,
+        }
+    }
+}
+
+impl Day16 {
+    /// Explains and exports a part's plan, as the options ask
+    fn report(&self, part: usize, raw: &ProblemRaw, num_rounds: Time, plan: &Plan) {
+        if self.explain {
+            eprintln!("{}", plan);
+        }
+        if let Some(dir) = &self.dot_dir {
+            let highlight = if self.explain { Some(plan) } else { None };
+            export_dot(dir, part, raw, num_rounds, highlight);
+        }
+    }
+}
+
+impl solution::Solution for Day16 {
+    type Input = ProblemRaw;
+    type Answer1 = FlowRate;
+    type Answer2 = FlowRate;
+
+    fn configure(&mut self, options: &solution::Options) -> Result<(), ParseError> {
+        self.explain = options.explain;
+        self.dot_dir = options.dot_dir.clone();
+        Ok(())
+    }
+
+    fn parse(&self, source: &str) -> Result<ProblemRaw, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, raw: &ProblemRaw) -> FlowRate {
+        let plan = plan_search::<1>(raw, self.rounds_p1);
+        self.report(1, raw, self.rounds_p1, &plan);
+        plan.total
+    }
+
+    fn part2(&self, raw: &ProblemRaw) -> FlowRate {
+        let plan = plan_subsets(raw, self.rounds_p2, self.agents_p2);
+        self.report(2, raw, self.rounds_p2, &plan);
+        plan.total
+    }
 }
 
 
@@ -414,12 +956,153 @@ Valve HH has flow rate=22; tunnel leads to valve GG
 Valve II has flow rate=0; tunnels lead to valves AA, JJ
 Valve JJ has flow rate=21; tunnel leads to valve II";
 
+    fn solve<const N_AGENTS: usize>(raw: &ProblemRaw, num_rounds: isize) -> FlowRate {
+        plan_search::<N_AGENTS>(raw, num_rounds).total
+    }
+
+    fn solve_subsets(raw: &ProblemRaw, num_rounds: isize, n_agents: usize) -> FlowRate {
+        plan_subsets(raw, num_rounds, n_agents).total
+    }
+
     #[test]
     fn test_example() {
-        assert_eq!(solve::<1>(EXAMPLE, 30), 1651);
+        assert_eq!(solve::<1>(&parse(EXAMPLE).unwrap(), 30), 1651);
     }
     #[test]
     fn test_example_p2() {
-        assert_eq!(solve::<2>(EXAMPLE, 26), 1707);
+        assert_eq!(solve::<2>(&parse(EXAMPLE).unwrap(), 26), 1707);
+    }
+
+    #[test]
+    fn test_disconnected() {
+        // KK can't be reached from AA, so can't be opened
+        let source = format!("{}\n{}\n{}", EXAMPLE,
+            "Valve KK has flow rate=50; tunnel leads to valve LL",
+            "Valve LL has flow rate=0; tunnel leads to valve KK");
+        let raw = parse(&source).unwrap();
+        assert_eq!(solve::<1>(&raw, 30), 1651);
+        assert_eq!(solve::<2>(&raw, 26), 1707);
+        assert_eq!(solve_subsets(&raw, 30, 1), 1651);
+        assert_eq!(solve_subsets(&raw, 26, 2), 1707);
+    }
+
+    #[test]
+    fn test_plan() {
+        let raw = parse(EXAMPLE).unwrap();
+        let plan = plan_subsets(&raw, 30, 1);
+        assert_eq!(plan.total, 1651);
+        let minutes: Vec<_> = plan.agents[0].iter()
+            .map(|o| (o.path.last().unwrap().as_str(), o.minute))
+            .collect();
+        assert_eq!(minutes, vec![("DD", 2), ("BB", 5), ("JJ", 9), ("HH", 17), ("EE", 21), ("CC", 24)]);
+        assert_eq!(plan.agents[0][3].path, vec!["II", "AA", "DD", "EE", "FF", "GG", "HH"]);
+        assert_eq!(plan.agents[0][0].pressure, 20*28);
+        assert!(plan.to_string().starts_with("Agent 1:\n  Minute  2: open DD, releasing 560 (AA -> DD)\n"));
+        assert!(plan.to_string().ends_with("Total pressure released: 1651"));
+
+        // The search finds the same plan
+        assert_eq!(plan_search::<1>(&raw, 30), plan);
+
+        let plan = plan_subsets(&raw, 26, 2);
+        assert_eq!(plan.total, 1707);
+        let mut opened: Vec<Vec<&str>> = plan.agents.iter()
+            .map(|a| a.iter().map(|o| o.path.last().unwrap().as_str()).collect())
+            .collect();
+        opened.sort();
+        assert_eq!(opened, vec![vec!["DD", "HH", "EE"], vec!["JJ", "BB", "CC"]]);
+        let total: FlowRate = plan.agents.iter().flatten().map(|o| o.pressure).sum();
+        assert_eq!(total, 1707);
+
+        let plan = plan_search::<2>(&raw, 26);
+        assert_eq!(plan.total, 1707);
+        let mut searched: Vec<Vec<&str>> = plan.agents.iter()
+            .map(|a| a.iter().map(|o| o.path.last().unwrap().as_str()).collect())
+            .collect();
+        searched.sort();
+        assert_eq!(searched, opened);
+    }
+
+    #[test]
+    fn test_dot() {
+        let raw = parse(EXAMPLE).unwrap();
+        let dot = raw_to_dot(&raw, None);
+        assert!(dot.starts_with("graph tunnels {\n    AA [label=\"AA\\nflow 0\"];\n"));
+        assert!(dot.contains("    AA -- DD [label=\"1\", len=1];\n"));
+        assert!(!dot.contains("DD -- AA"));
+        assert_eq!(dot.matches(" -- ").count(), 10);
+        assert!(dot.ends_with("}\n"));
+
+        let plan = plan_subsets(&raw, 26, 2);
+        let problem = reduce_problem(&raw, 26);
+        let dot = reduced_to_dot(&problem, Some(&plan));
+        // AA and the six valves, all connected
+        assert_eq!(dot.matches(" -- ").count(), 7*6/2);
+        assert!(dot.contains("AA -- DD [label=\"1\", len=1, color="));
+        assert!(dot.contains("EE -- HH [label=\"3\", len=3, color="));
+        assert!(!dot.contains("BB -- HH [label=\"6\", len=6, color="));
+        assert!(dot.contains("style=filled, xlabel=\"minute 11\""));
+
+        // Every tunnel on the way to HH is highlighted
+        let dot = raw_to_dot(&raw, Some(&plan));
+        for tunnel in ["EE -- FF", "FF -- GG", "GG -- HH"] {
+            let line = dot.lines().find(|l| l.contains(tunnel)).unwrap();
+            assert!(line.contains("penwidth=3"));
+        }
+    }
+
+    #[test]
+    fn test_dot_option() {
+        let dir = std::env::temp_dir().join(format!("aoc22_d16_dot_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut options = solution::Options { dot_dir: Some(dir.clone()), ..Default::default() };
+        solution::run::<Day16>(EXAMPLE, crate::Part::Both, crate::input::Variant::Example, &options).unwrap();
+        let names = ["d16_raw_p1.dot", "d16_reduced_p1.dot", "d16_raw_p2.dot", "d16_reduced_p2.dot"];
+        for name in names {
+            let dot = std::fs::read_to_string(dir.join(name)).unwrap();
+            assert!(dot.starts_with("graph tunnels {\n"));
+            assert!(!dot.contains("penwidth"));
+        }
+
+        // Explaining highlights the plan too
+        options.explain = true;
+        solution::run::<Day16>(EXAMPLE, crate::Part::Two, crate::input::Variant::Example, &options).unwrap();
+        let dot = std::fs::read_to_string(dir.join("d16_reduced_p2.dot")).unwrap();
+        assert!(dot.contains("AA -- DD [label=\"1\", len=1, color="));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_actual() {
+        let input =
+            std::fs::read_to_string("data/d16.txt")
+            .expect("Failed to read input");
+        let raw = parse(&input).unwrap();
+        assert_eq!(solve::<1>(&raw, 30), 1880);
+        assert_eq!(solve::<2>(&raw, 26), 2520);
+        assert_eq!(solve_subsets(&raw, 30, 1), 1880);
+        assert_eq!(solve_subsets(&raw, 26, 2), 2520);
+    }
+
+    #[test]
+    fn test_subsets() {
+        let raw = parse(EXAMPLE).unwrap();
+        assert_eq!(solve_subsets(&raw, 30, 1), 1651);
+        assert_eq!(solve_subsets(&raw, 26, 2), 1707);
+        for rounds in [5, 10, 20] {
+            assert_eq!(solve_subsets(&raw, rounds, 3), solve::<3>(&raw, rounds));
+        }
+        // Enough agents to open every valve on the way past
+        assert_eq!(solve_subsets(&raw, 26, 6), solve_subsets(&raw, 26, 10));
+        assert_eq!(solve_subsets(&raw, 26, 0), 0);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let source = "Valve AA has flow rate=0; tunnels lead to valves BB, CC
+Valve BB has flow rate=13; tunnel leads to valve AA";
+        let err = parse(source).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 54));
+        assert_eq!(err.expected, "a known room code");
+        assert_eq!(err.found, "CC");
     }
 }
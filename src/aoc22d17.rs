@@ -1,99 +1,171 @@
 
-use crate::arr2d::Array2d;
+use crate::bit_grid::BitGrid;
+use crate::cycle::CycleDetector;
+use crate::log;
+use crate::solution::{Options, Solution};
+use crate::parse_utils::*;
 
 // All coords are first X, then Y.
 // X increases from left to right
 // Y increases from bottom to top
 
 
-fn make_rocks() -> [Array2d<bool>; 5] {
-    // Each rock appears so that its left edge is two units away from the left
-    // wall and its bottom edge is three units above the highest rock in the
-    // room (or the floor, if there isn't one).
-    // 
-    // This means that the shapes should be left-bottom aligned 
-    
-    // ####
-    let rock1 = Array2d::newu(true, 4, 1);
-
-    // .#.
-    // ###
-    // .#.
-    let mut rock2 = Array2d::newu(false, 3, 3);
-    *rock2.get_mut((1,0)) = true;
-    *rock2.get_mut((0,1)) = true;
-    *rock2.get_mut((1,1)) = true;
-    *rock2.get_mut((2,1)) = true;
-    *rock2.get_mut((1,2)) = true;
-
-    // ..#
-    // ..#
-    // ###
-    let mut rock3 = Array2d::newu(false, 3, 3);
-    *rock3.get_mut((0,0)) = true;
-    *rock3.get_mut((1,0)) = true;
-    *rock3.get_mut((2,0)) = true;
-    *rock3.get_mut((2,1)) = true;
-    *rock3.get_mut((2,2)) = true;
-
-    // #
-    // #
-    // #
-    // #
-    let rock4 = Array2d::newu(true, 1, 4);
-     
-    // ##
-    // ##
-    let rock5 = Array2d::newu(true, 2, 2);
-    [rock1, rock2, rock3, rock4, rock5]
+/// A rock as a bit mask per row, bottom row first, with bit x for column x
+///
+/// Masks line up with the rows of the shaft's BitGrid, so a rock row can be
+/// checked against a shaft row with a single AND.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Rock {
+    rows: Vec<u64>,
+    width: isize,
+}
+
+/// The rocks from the puzzle, in the order they fall
+const ROCKS: &str = "\
+####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##
+";
+
+/// Parses a set of rocks drawn with '#' for rock and '.' for air
+///
+/// Rocks are separated by blank lines and fall in the order given. Each
+/// drawing is top down like in the puzzle text. Rows and columns with no
+/// rock are trimmed, so a rock is left-bottom aligned however it's drawn.
+pub fn parse_rocks(src: &str) -> Result<Vec<Rock>, ParseError> {
+    Ok(parse_rocks_at(src)?.into_iter().map(|(_, rock)| rock).collect())
 }
 
+/// Like parse_rocks, but also returns where each drawing starts
+fn parse_rocks_at(src: &str) -> Result<Vec<(usize, Rock)>, ParseError> {
+    let mut it = get_byte_iterator(src);
+    let mut rocks = Vec::new();
+    loop {
+        while try_consume_sequence(src, &mut it, "\n")? { }
+        let start = match it.peek() {
+            Some((index, _)) => *index,
+            None => { break; },
+        };
+        rocks.push((start, parse_rock(src, &mut it)?));
+    }
+    if rocks.is_empty() {
+        return Err(error_at(src, &mut it, "at least one rock"));
+    }
+    Ok(rocks)
+}
 
-fn parse(source: &str) -> Vec<isize> {
-    let mut output = Vec::new();
-    for c in source.chars() {
-        match c {
-            ' ' => { },
-            '\n' => { },
-            '<' => { output.push(-1); }
-            '>' => { output.push( 1); }
-            _ => {
-                panic!("Unexpected character in input: {}", c);
+/// Parses a single drawing, up to a blank line or the end of input
+fn parse_rock(src: &str, it: &mut ByteIterator) -> Result<Rock, ParseError> {
+    let is_cell = |b| b == b'#' || b == b'.';
+    let start = it.peek().unwrap().0;
+    let mut drawing: Vec<u64> = Vec::new();
+    let mut drawn_width = 0;
+    while let Some((line_start, _)) = it.peek() {
+        let line_start = *line_start;
+        let line = take_while(src, it, is_cell);
+        match it.peek() {
+            Some((_, b'\n')) | None => { },
+            Some(_) => {
+                return Err(error_at(src, it, "'#', '.' or end of line"));
             }
         }
+        if line.is_empty() {
+            // Blank line, end of drawing
+            break;
+        }
+        if line.len() > 64 {
+            return Err(ParseError::new(
+                src, line_start, "a rock at most 64 wide", &format!("a row of width {}", line.len())));
+        }
+        if !drawing.is_empty() && line.len() != drawn_width {
+            return Err(ParseError::new(
+                src,
+                line_start,
+                &format!("a row of width {}", drawn_width),
+                &format!("a row of width {}", line.len())));
+        }
+        it.next();
+        drawn_width = line.len();
+        drawing.push(line.bytes().enumerate()
+            .filter(|(_, b)| *b == b'#')
+            .fold(0, |mask, (x, _)| mask | 1 << x));
     }
-    output
+
+    // Drawings are top down, but Y increases upwards
+    let mut rows: Vec<u64> = drawing.into_iter().rev().skip_while(|r| *r == 0).collect();
+    while rows.last() == Some(&0) {
+        rows.pop();
+    }
+    let all = rows.iter().fold(0, |a, r| a | r);
+    if all == 0 {
+        return Err(ParseError::new(src, start, "a rock with at least one '#'", "only air"));
+    }
+    let shift = all.trailing_zeros();
+    Ok(Rock {
+        rows: rows.iter().map(|r| r >> shift).collect(),
+        width: (64 - (all >> shift).leading_zeros()) as isize,
+    })
+}
+
+
+/// Parses the jet pattern, which may be split over several lines
+fn parse(source: &str) -> Result<Vec<isize>, ParseError> {
+    let lines = parse_lines(source, |src, it| {
+        let jets = take_while(src, it, |b| b == b'<' || b == b'>');
+        match it.peek() {
+            Some((_, b'\n')) | None => { },
+            Some(_) => {
+                return Err(error_at(src, it, "'<', '>' or end of line"));
+            }
+        }
+        Ok(jets.bytes().map(|b| if b == b'<' { -1 } else { 1 }).collect::<Vec<isize>>())
+    })?;
+    let dirs = lines.concat();
+    if dirs.is_empty() {
+        return Err(ParseError::new(source, source.len(), "at least one jet", "end of input"));
+    }
+    Ok(dirs)
 }
 
 
-fn place_rock(rock: &Array2d<bool>, state: &mut State) {
-    let (rsx, rsy) = rock.size_i(); // Rock Size x|y
+fn place_rock(rock: &Rock, state: &mut State) {
+    let rsy = rock.rows.len(); // Rock Size y
+    let rock_y = state.rock_y as usize;
     // Increase shaft if necessary
-    while state.rock_y + rsy > state.shaft.size_i().1 {
-        state.shaft.add_row((0..state.shaft.size_i().0).map(|_| false));
+    while rock_y + rsy > state.shaft.height() {
+        state.shaft.add_row();
     }
 
     // Place
-    for ry in 0..rsy {
-        for rx in 0..rsx {
-            if *rock.get((rx,ry)) {
-                let v = state.shaft.get_mut((state.rock_x+rx, state.rock_y+ry));
-                if *v {
-                    panic!("Placing rock over existing rock");
-                }
-                *v = true;
-            }
+    for (ry, row) in rock.rows.iter().enumerate() {
+        let mask = [row << state.rock_x];
+        if state.shaft.row_intersects(rock_y + ry, &mask) {
+            panic!("Placing rock over existing rock");
         }
+        state.shaft.or_row(rock_y + ry, &mask);
     }
 }
 
-fn can_move_to(x: isize, y: isize, rock: &Array2d<bool>, state: &State) -> bool {
-    let (rsx, rsy) = rock.size_i(); // Rock Size x|y
-    let (ssx, ssy) = state.shaft.size_i(); // Shaft Size x|y
+fn can_move_to(x: isize, y: isize, rock: &Rock, state: &State) -> bool {
+    let ssx = state.shaft.width() as isize; // Shaft Size x
     
     // Check bounds
     if x < 0 { return false; }
-    if x + rsx > ssx { return false; }
+    if x + rock.width > ssx { return false; }
     if y < 0 {
         if state.purged_rows > 0 {
             panic!("Hit bottom but we've purged rows");
@@ -101,15 +173,10 @@ fn can_move_to(x: isize, y: isize, rock: &Array2d<bool>, state: &State) -> bool
         return false;
     }
 
-    // Check space
-    for ry in 0..rsy {
-        // No need to check above shaft
-        if y + ry >= ssy { continue; }
-
-        for rx in 0..rsx {
-            if *rock.get((rx, ry)) && *state.shaft.get((x+rx, y+ry)) {
-                return false;
-            }
+    // Check space a row at a time, anything above the shaft is empty
+    for (ry, row) in rock.rows.iter().enumerate() {
+        if state.shaft.row_intersects(y as usize + ry, &[row << x]) {
+            return false;
         }
     }
 
@@ -117,59 +184,175 @@ fn can_move_to(x: isize, y: isize, rock: &Array2d<bool>, state: &State) -> bool
     true
 }
 
-#[allow(dead_code)]
-fn draw_shaft(shaft: &Array2d<bool>) {
-    for y in (0..shaft.size_i().1).rev() {
-        print!("|");
-        for x in 0..shaft.size_i().0 {
-            if *shaft.get((x,y)) {
-                print!("#");
+/// What's at (x, y) in the shaft, including the falling rock
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum Cell {
+    Air,
+    Settled,
+    Falling,
+}
+
+fn cell_at(state: &State, rocks: &[Rock], x: isize, y: isize) -> Cell {
+    if state.shaft.try_get((x, y)) == Some(true) {
+        return Cell::Settled;
+    }
+    let rock = &rocks[state.current_rock % rocks.len()];
+    let (rx, ry) = (x - state.rock_x, y - state.rock_y);
+    if 0 <= rx && 0 <= ry && (ry as usize) < rock.rows.len() && rock.rows[ry as usize] >> rx & 1 == 1 {
+        return Cell::Falling;
+    }
+    Cell::Air
+}
+
+/// Rows needed to show the shaft and the falling rock
+fn frame_height(state: &State, rocks: &[Rock]) -> usize {
+    let rock = &rocks[state.current_rock % rocks.len()];
+    std::cmp::max(state.shaft.height(), state.rock_y as usize + rock.rows.len())
+}
+
+/// Draws the shaft like the puzzle text, '@' for the falling rock
+///
+/// Once rows have been purged the floor is drawn with '~' and followed by
+/// how many rows are missing below it.
+pub fn render_text(state: &State, rocks: &[Rock]) -> String {
+    let mut output = String::new();
+    for y in (0..frame_height(state, rocks) as isize).rev() {
+        output.push('|');
+        for x in 0..state.shaft.width() as isize {
+            output.push(match cell_at(state, rocks, x, y) {
+                Cell::Air => '.',
+                Cell::Settled => '#',
+                Cell::Falling => '@',
+            });
+        }
+        output.push_str("|\n");
+    }
+    let floor = if state.purged_rows > 0 { '~' } else { '-' };
+    output.push('+');
+    for _ in 0..state.shaft.width() { output.push(floor); }
+    output.push('+');
+    if state.purged_rows > 0 {
+        output.push_str(&format!(" {} rows purged", state.purged_rows));
+    }
+    output.push('\n');
+    output
+}
+
+/// Draws the shaft as a binary PPM image, scale pixels per cell
+///
+/// Walls and floor are a cell thick, with the floor red once rows have been
+/// purged.
+pub fn render_ppm(state: &State, rocks: &[Rock], scale: usize) -> Vec<u8> {
+    const AIR: [u8; 3] = [16, 16, 32];
+    const SETTLED: [u8; 3] = [160, 160, 160];
+    const FALLING: [u8; 3] = [255, 160, 0];
+    const WALL: [u8; 3] = [96, 64, 32];
+    const PURGED: [u8; 3] = [200, 0, 0];
+
+    let cells_x = state.shaft.width() + 2;
+    let cells_y = frame_height(state, rocks) + 1;
+    let mut output = format!("P6\n{} {}\n255\n", cells_x*scale, cells_y*scale).into_bytes();
+    for cy in 0..cells_y {
+        let mut line = Vec::with_capacity(cells_x*scale*3);
+        // Image rows go top down, y is one less than the cells from the bottom
+        let y = cells_y as isize - 2 - cy as isize;
+        for cx in 0..cells_x {
+            let x = cx as isize - 1;
+            let colour = if y < 0 {
+                if state.purged_rows > 0 { PURGED } else { WALL }
+            } else if x < 0 || x >= state.shaft.width() as isize {
+                WALL
             } else {
-                print!(".");
+                match cell_at(state, rocks, x, y) {
+                    Cell::Air => AIR,
+                    Cell::Settled => SETTLED,
+                    Cell::Falling => FALLING,
+                }
+            };
+            for _ in 0..scale {
+                line.extend_from_slice(&colour);
             }
         }
-        print!("|\n");
+        for _ in 0..scale {
+            output.extend_from_slice(&line);
+        }
     }
-    print!("+");
-    for _ in 0..shaft.size_i().0 { print!("-"); }
-    print!("+\n");
+    output
 }
 
+/// How to write frames of the shaft
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum FrameFormat {
+    /// Like the puzzle text, see render_text
+    Text,
+    /// PPM image with scale pixels per cell, see render_ppm
+    Ppm { scale: usize },
+}
 
-/// Tracks what locations are accessible and purges to that point
-fn check_purge(state: &mut State) {
-    // All locations are accessible at the top
-    for a in &mut state.accessible {
-        *a = true;
+/// Writes one frame to path, adding the extension for the format
+pub fn write_frame(path: &std::path::Path, state: &State, rocks: &[Rock], format: FrameFormat)
+    -> std::io::Result<()>
+{
+    match format {
+        FrameFormat::Text => std::fs::write(path.with_extension("txt"), render_text(state, rocks)),
+        FrameFormat::Ppm { scale } => {
+            std::fs::write(path.with_extension("ppm"), render_ppm(state, rocks, scale))
+        },
     }
-    let mut inaccessible_line = None;
-    'line_loop: for y in (2..state.shaft.size_i().1).rev() {
-        // Grow for sideways movement
-        for x in 0..(state.shaft.size_u().0-1) {
-            state.accessible[x] = state.accessible[x] || state.accessible[x+1];
-        }
-        for x in (1..state.shaft.size_u().0).rev() {
-            if x > 0 {
-                state.accessible[x] = state.accessible[x] || state.accessible[x-1];
-            }
+}
+
+/// Drops n_rocks and writes a frame to dir for every jet push
+///
+/// Frames are numbered frame_000000, frame_000001, ... starting with the
+/// empty shaft. Purging applies as in solve, so this shows what check_purge
+/// removes and when.
+pub fn dump_animation(
+        dirs: &[isize],
+        config: &Day17,
+        n_rocks: usize,
+        dir: &std::path::Path,
+        format: FrameFormat)
+    -> std::io::Result<usize>
+{
+    config.check();
+    std::fs::create_dir_all(dir)?;
+    let mut state = new_state(config);
+    let mut n_frames = 0;
+    loop {
+        let path = dir.join(format!("frame_{:06}", n_frames));
+        write_frame(&path, &state, &config.rocks, format)?;
+        n_frames += 1;
+        if state.current_rock >= n_rocks {
+            return Ok(n_frames);
         }
+        step(&mut state, config, dirs);
+    }
+}
+
+
+/// Finds the highest row no falling rock can reach any part of
+///
+/// Everything below it (and it) can no longer affect the simulation.
+fn find_inaccessible_line(shaft: &BitGrid) -> Option<usize> {
+    // All locations are accessible at the top
+    let full = shaft.full_row()[0];
+    let mut accessible = full;
+    for y in (2..shaft.height()).rev() {
+        // Grow for sideways movement
+        accessible = (accessible | accessible << 1 | accessible >> 1) & full;
         // Block out solids
-        for x in 0..state.shaft.size_i().0 {
-            if *state.shaft.get((x,y)) {
-                state.accessible[x as usize] = false;
-            }
-        }
+        accessible &= !shaft.row(y)[0];
         // Check if any accessible
-        for a in &state.accessible {
-            if *a {
-                continue 'line_loop;
-            }
+        if accessible == 0 {
+            return Some(y);
         }
-
-        // None are accessible!
-        inaccessible_line = Some(y);
-        break;
     }
+    None
+}
+
+/// Tracks what locations are accessible and purges to that point
+fn check_purge(state: &mut State) {
+    let inaccessible_line = find_inaccessible_line(&state.shaft);
 
     let remove_up_to =
         match inaccessible_line {
@@ -180,14 +363,14 @@ fn check_purge(state: &mut State) {
         };
     
     // PURGE!
-    state.purged_rows += remove_up_to;
+    state.purged_rows += remove_up_to as isize;
     state.shaft.drop_front(remove_up_to);
 }
 
-#[derive(Clone)]
-struct State {
+/// The simulation partway through, between any two jet pushes
+pub struct State {
     // Where rocks are in the shaft
-    shaft: Array2d<bool>,
+    shaft: BitGrid,
     // Index of the current rock shape
     current_rock: usize,
     // Index of the current jet direcion
@@ -195,138 +378,192 @@ struct State {
     // Position of the falling rock
     rock_x: isize,
     rock_y: isize,
-    // A buffer for checking if things are accessible
-    accessible: Vec<bool>,
     // Allow us to purge no longer relevant rows
     purged_rows: isize,
 }
 
-fn check_state_equivalent(a: &State, b: &State) -> bool {
-    if a.current_rock % 5 != b.current_rock % 5 { return false; }
-    if a.current_dir != b.current_dir { return false; }
-    if a.rock_x != b.rock_x { return false; }
-    if a.rock_y != b.rock_y { return false; }
-    // Check shaft last as it's the most expensive
-    if a.shaft != b.shaft { return false; }
+/// Everything that decides what happens next, taken between rocks
+///
+/// That's which rock and jet are next and the surface profile, the rows a
+/// rock could still reach. The falling rock always starts at the same place
+/// relative to the top so doesn't need including.
+type Fingerprint = (usize, usize, Vec<u64>);
+
+fn fingerprint(state: &State, n_shapes: usize) -> Fingerprint {
+    let from = find_inaccessible_line(&state.shaft).unwrap_or_default();
+    let profile = (from..state.shaft.height())
+        .flat_map(|y| state.shaft.row(y).iter().copied())
+        .collect();
+    (state.current_rock % n_shapes, state.current_dir, profile)
+}
 
-    return true;
+pub fn new_state(config: &Day17) -> State {
+    State {
+        shaft: BitGrid::new(config.width as usize, 0),
+        current_rock: 0,
+        current_dir: 0,
+        rock_x: config.spawn_x,
+        rock_y: config.spawn_gap,
+        purged_rows: 0,
+    }
 }
 
-fn drop_rock(state: &mut State, rocks: &[Array2d<bool>; 5], dirs: &Vec<isize>, allow_purge: bool)
-    -> isize
-{
-    loop {
-        let dir = dirs[state.current_dir];
-        state.current_dir = (state.current_dir + 1) % dirs.len();
+/// One jet push and fall, returns true if the rock came to rest
+pub fn step(state: &mut State, config: &Day17, dirs: &[isize]) -> bool {
+    let rocks = &config.rocks;
+    let dir = dirs[state.current_dir];
+    state.current_dir = (state.current_dir + 1) % dirs.len();
 
-        let rock = &rocks[state.current_rock % rocks.len()];
-        if can_move_to(state.rock_x+dir, state.rock_y, rock, state) {
-            state.rock_x += dir;
-        }
-        
-        if can_move_to(state.rock_x, state.rock_y-1, rock, state) {
-            state.rock_y -= 1;
-        } else {
-            place_rock(rock, state);
-            if allow_purge {
-                check_purge(state);
-            }
+    let rock = &rocks[state.current_rock % rocks.len()];
+    if can_move_to(state.rock_x+dir, state.rock_y, rock, state) {
+        state.rock_x += dir;
+    }
 
-            state.current_rock += 1;
-            state.rock_x = 2;
-            state.rock_y = state.shaft.size_i().1 + 3; // 3 units above bottom
-            break;
+    if can_move_to(state.rock_x, state.rock_y-1, rock, state) {
+        state.rock_y -= 1;
+        false
+    } else {
+        place_rock(rock, state);
+        if config.allow_purge {
+            check_purge(state);
         }
+
+        state.current_rock += 1;
+        state.rock_x = config.spawn_x;
+        state.rock_y = state.shaft.height() as isize + config.spawn_gap;
+        true
     }
-    return state.shaft.size_i().1 + state.purged_rows
 }
 
-fn solve(input: &str, width: isize, n_rocks: usize, allow_purge: bool) -> isize {
+fn drop_rock(state: &mut State, config: &Day17, dirs: &[isize]) -> isize {
+    while !step(state, config, dirs) { }
+    state.shaft.height() as isize + state.purged_rows
+}
+
+fn solve(dirs: &[isize], config: &Day17, n_rocks: usize) -> isize {
     // Periodicity!
     //
-    //  - Run for a while (10,000 rocks or so)
-    //  - Remember the state
-    //  - Advance, checking if the state looks the same as the 10,000th
-    //  - Determine how many extra lines were added
+    //  - Drop rocks, fingerprinting the state after each one
+    //  - Once a fingerprint repeats, the heights repeat with it
+    //  - Skip ahead by whole cycles using how many rows each cycle adds
     //  - Shazam!
     //
-    let mut state = State {
-        shaft: Array2d::new(false, width, 0),
-        current_rock: 0,
-        current_dir: 0,
-        rock_x: 2,
-        rock_y: 3,
-        accessible: Vec::with_capacity(width as usize),
-        purged_rows: 0,
-    };
+    config.check();
+    let mut state = new_state(config);
 
-    let rocks = make_rocks();
-    let dirs = parse(input);
+    let rocks = &config.rocks;
 
-    for _ in 0..width {
-        state.accessible.push(false);
+    // heights[n] is the height of the tower after n rocks
+    let mut heights = vec![0];
+    let mut detector = CycleDetector::new();
+    detector.observe(0, fingerprint(&state, rocks.len()));
+    while state.current_rock < n_rocks {
+        heights.push(drop_rock(&mut state, config, dirs));
+        if let Some(cycle) = detector.observe(state.current_rock, fingerprint(&state, rocks.len())) {
+            log::info!(
+                "Cycle exploited\n Start: {}\n Period: {}",
+                cycle.start,
+                cycle.period);
+            return cycle.extrapolate(n_rocks, &heights);
+        }
     }
 
-    // If it's a small number of rocks, just drop em
-    if n_rocks < 10000 {
-        while state.current_rock < n_rocks {
-            drop_rock(&mut state, &rocks, &dirs, allow_purge);
-        }
+    state.shaft.height() as isize + state.purged_rows
+}
 
-        // draw_shaft(&state.shaft);
-        return state.shaft.size_i().1 + state.purged_rows;
-    }
 
-    if !allow_purge {
-        panic!("Must allow purge for large requests");
-    }
+pub struct Day17 {
+    /// Width of the shaft
+    pub width: isize,
+    /// Shapes that fall, in order, see parse_rocks
+    pub rocks: Vec<Rock>,
+    /// Gap between the left wall and a new rock's left edge
+    pub spawn_x: isize,
+    /// Gap between the top of the tower and a new rock's bottom edge
+    pub spawn_gap: isize,
+    pub n_rocks_p1: usize,
+    pub n_rocks_p2: usize,
+    /// Drop rows that no rock can reach any more
+    pub allow_purge: bool,
+    /// Write a frame here for every jet push in part 1
+    pub frames_dir: Option<std::path::PathBuf>,
+    pub frame_format: FrameFormat,
+}
 
-    // Drop 10k rocks
-    while state.current_rock < 10000 {
-        drop_rock(&mut state, &rocks, &dirs, allow_purge);
+impl Default for Day17 {
+    fn default() -> Day17 {
+        Day17 {
+            width: 7,
+            rocks: parse_rocks(ROCKS).expect("Malformed built in rocks"),
+            spawn_x: 2,
+            spawn_gap: 3,
+            n_rocks_p1: 2022,
+            n_rocks_p2: 1000000000000,
+            allow_purge: true,
+            frames_dir: None,
+            frame_format: FrameFormat::Text,
+        }
     }
-    let state2 = state.clone();
-    
-    // Look for a repeating pattern
-    loop {
-        if state.current_rock >= n_rocks {
-            return state.shaft.size_i().1 + state.purged_rows;
+}
+
+impl Day17 {
+    /// Panics unless the shaft can hold every rock where it appears
+    fn check(&self) {
+        if self.width > 64 {
+            panic!("Shafts are stored a word per row, so can't be wider than 64");
         }
-        
-        drop_rock(&mut state, &rocks, &dirs, allow_purge);
-        if check_state_equivalent(&state, &state2) {
-            break;
+        if self.rocks.is_empty() {
+            panic!("Need at least one rock to drop");
+        }
+        if self.rocks.iter().any(|r| self.spawn_x < 0 || self.spawn_x + r.width > self.width) {
+            panic!("Rocks must fit in the shaft where they appear");
         }
     }
+}
 
-    let cycle_period = state.current_rock - state2.current_rock;
-    let cycle_rows = state.purged_rows - state2.purged_rows;
+impl Solution for Day17 {
+    type Input = Vec<isize>;
+    type Answer1 = isize;
+    type Answer2 = isize;
 
-    let needed_rocks = n_rocks - state.current_rock;
-    let num_cycles = needed_rocks / cycle_period;
-    
-    state.purged_rows += cycle_rows * num_cycles as isize;
-    state.current_rock += cycle_period * num_cycles;
-        
-    println!(
-        "Cycle exploited\n Period: {}\n New rock num: {}\n Remaining rocks: {}",
-        cycle_period,
-        state.current_rock,
-        n_rocks - state.current_rock);
-    while state.current_rock < n_rocks {
-        drop_rock(&mut state, &rocks, &dirs, allow_purge);
+    fn configure(&mut self, options: &Options) -> Result<(), ParseError> {
+        self.frames_dir = options.frames_dir.clone();
+        if let Some(scale) = options.frame_scale {
+            self.frame_format = FrameFormat::Ppm { scale };
+        }
+        if let Some(src) = &options.rocks {
+            // Catch rocks that don't fit here, where we can say which one
+            let rocks = parse_rocks_at(src)?;
+            let room = self.width - self.spawn_x;
+            for (start, rock) in &rocks {
+                if rock.width > room {
+                    return Err(ParseError::new(
+                        src, *start, &format!("a rock at most {} wide", room),
+                        &format!("a rock {} wide", rock.width)));
+                }
+            }
+            self.rocks = rocks.into_iter().map(|(_, rock)| rock).collect();
+        }
+        Ok(())
     }
 
-    return state.shaft.size_i().1 + state.purged_rows;
-}
+    fn parse(&self, source: &str) -> Result<Vec<isize>, ParseError> {
+        parse(source)
+    }
 
+    fn part1(&self, dirs: &Vec<isize>) -> isize {
+        if let Some(dir) = &self.frames_dir {
+            match dump_animation(dirs, self, self.n_rocks_p1, dir, self.frame_format) {
+                Ok(n_frames) => { log::info!("Wrote {} frames to {}", n_frames, dir.display()); },
+                Err(e) => { eprintln!("Failed to write frames to {}: {}", dir.display(), e); },
+            }
+        }
+        solve(dirs, self, self.n_rocks_p1)
+    }
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d17.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input, 7, 2022, true));
-    println!("{}", solve(&input, 7, 1000000000000, true));
+    fn part2(&self, dirs: &Vec<isize>) -> isize {
+        solve(dirs, self, self.n_rocks_p2)
+    }
 }
 
 
@@ -337,10 +574,25 @@ mod tests {
     
     const EXAMPLE: &'static str = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
 
+    fn config(allow_purge: bool) -> Day17 {
+        Day17 { allow_purge, ..Default::default() }
+    }
+
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE, 7, 2022, false), 3068);
-        assert_eq!(solve(EXAMPLE, 7, 2022, true), 3068);
+        let dirs = parse(EXAMPLE).unwrap();
+        assert_eq!(solve(&dirs, &config(false), 2022), 3068);
+        assert_eq!(solve(&dirs, &config(true), 2022), 3068);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(parse(">><\n<>\n").unwrap(), vec![1, 1, -1, -1, 1]);
+        let err = parse(">><\n<x>\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+        assert_eq!(err.expected, "'<', '>' or end of line");
+        let err = parse("\n").unwrap_err();
+        assert_eq!(err.expected, "at least one jet");
     }
 
     #[test]
@@ -348,18 +600,183 @@ mod tests {
         let input =
             std::fs::read_to_string("data/d17.txt")
             .expect("Failed to read input");
+        let dirs = parse(&input).unwrap();
 
         for v in [200, 250, 353, 354, 400] {
             println!("Test {}", v);
-            assert_eq!(solve(&input, 7, v, false), solve(&input, 7, v, true));
+            assert_eq!(solve(&dirs, &config(false), v), solve(&dirs, &config(true), v));
         }
 
-        assert_eq!(solve(&input, 7, 2022, false), 3067);
-        assert_eq!(solve(&input, 7, 2022, true), 3067);
+        assert_eq!(solve(&dirs, &config(false), 2022), 3067);
+        assert_eq!(solve(&dirs, &config(true), 2022), 3067);
     }
 
     #[test]
     fn test_example_p2() {
-        assert_eq!(solve(EXAMPLE, 7, 1000000000000, true), 1514285714288);
+        assert_eq!(solve(&parse(EXAMPLE).unwrap(), &config(true), 1000000000000), 1514285714288);
+        assert_eq!(solve(&parse(EXAMPLE).unwrap(), &config(false), 1000000000000), 1514285714288);
+    }
+
+    /// Drops every rock without looking for cycles
+    fn simulate(dirs: &[isize], n_rocks: usize) -> isize {
+        let config = config(true);
+        let mut state = new_state(&config);
+        let mut height = 0;
+        while state.current_rock < n_rocks {
+            height = drop_rock(&mut state, &config, dirs);
+        }
+        height
+    }
+
+    #[test]
+    fn test_cycle_matches_simulation() {
+        let dirs = parse(EXAMPLE).unwrap();
+        for n in [0, 1, 15, 100, 1234, 5000] {
+            assert_eq!(solve(&dirs, &config(true), n), simulate(&dirs, n));
+        }
+    }
+
+    #[test]
+    fn test_parse_rocks() {
+        let rocks = parse_rocks(ROCKS).unwrap();
+        assert_eq!(rocks.len(), 5);
+        assert_eq!(rocks[0], Rock { rows: vec![0b1111], width: 4 });
+        assert_eq!(rocks[1], Rock { rows: vec![0b010, 0b111, 0b010], width: 3 });
+        // Bottom row first
+        assert_eq!(rocks[2], Rock { rows: vec![0b111, 0b100, 0b100], width: 3 });
+
+        // Padding is trimmed
+        let rocks = parse_rocks("\n\n....\n.##.\n..#.\n....\n\n\n#").unwrap();
+        assert_eq!(rocks, vec![
+            Rock { rows: vec![0b10, 0b11], width: 2 },
+            Rock { rows: vec![0b1], width: 1 },
+        ]);
+
+        let err = parse_rocks("##\n#\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.expected, "a row of width 2");
+        let err = parse_rocks("#.\n#x\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+        let err = parse_rocks("#\n\nx#\n").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+        let err = parse_rocks("#\n\n..\n").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+        assert!(parse_rocks("\n\n").is_err());
+    }
+
+    #[test]
+    fn test_variant() {
+        // Only flat rocks in a shaft exactly their width stack one row each
+        let config = Day17 {
+            width: 4,
+            rocks: parse_rocks("####").unwrap(),
+            spawn_x: 0,
+            spawn_gap: 5,
+            ..Default::default()
+        };
+        assert_eq!(solve(&parse(EXAMPLE).unwrap(), &config, 1000), 1000);
+
+        // Squares in a shaft of width 2 stack two rows each
+        let config = Day17 {
+            width: 2,
+            rocks: parse_rocks("##\n##\n\n##\n##").unwrap(),
+            spawn_x: 0,
+            ..Default::default()
+        };
+        assert_eq!(solve(&parse(EXAMPLE).unwrap(), &config, 1000000000000), 2000000000000);
+    }
+
+    #[test]
+    fn test_render() {
+        let dirs = parse(EXAMPLE).unwrap();
+        let config = config(false);
+        let mut state = new_state(&config);
+        drop_rock(&mut state, &config, &dirs);
+        assert_eq!(render_text(&state, &config.rocks), "\
+|...@...|
+|..@@@..|
+|...@...|
+|.......|
+|.......|
+|.......|
+|..####.|
++-------+
+");
+
+        // Falling rock part way down
+        step(&mut state, &config, &dirs);
+        step(&mut state, &config, &dirs);
+        assert_eq!(render_text(&state, &config.rocks), "\
+|...@...|
+|..@@@..|
+|...@...|
+|.......|
+|..####.|
++-------+
+");
+
+        // 7 wide plus walls by 5 tall plus floor
+        let image = render_ppm(&state, &config.rocks, 2);
+        let header = "P6\n18 12\n255\n";
+        assert!(image.starts_with(header.as_bytes()));
+        assert_eq!(image.len(), header.len() + 18*12*3);
+
+        // Purged rows are noted below the floor
+        let config = Day17 { width: 4, spawn_x: 0, ..config };
+        let mut state = new_state(&config);
+        state.shaft.add_row();
+        state.shaft.or_row(0, &[0b1111]);
+        state.purged_rows = 10;
+        assert!(render_text(&state, &config.rocks).ends_with("+~~~~+ 10 rows purged\n"));
+    }
+
+    #[test]
+    fn test_dump_animation() {
+        let dir = std::env::temp_dir().join(format!("aoc22_d17_frames_{}", std::process::id()));
+        let dirs = parse(EXAMPLE).unwrap();
+        let n_frames = dump_animation(&dirs, &config(true), 1, &dir, FrameFormat::Text).unwrap();
+        // The empty shaft then the first rock takes 4 pushes to land
+        assert_eq!(n_frames, 5);
+        let first = std::fs::read_to_string(dir.join("frame_000000.txt")).unwrap();
+        assert!(first.starts_with("|..@@@@.|\n|.......|\n"));
+        let last = std::fs::read_to_string(dir.join("frame_000004.txt")).unwrap();
+        assert!(last.starts_with("|...@...|\n"));
+        assert!(last.ends_with("|..####.|\n+-------+\n"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 5);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_frames_option() {
+        let dir = std::env::temp_dir().join(format!("aoc22_d17_option_{}", std::process::id()));
+        let mut day = Day17 { n_rocks_p1: 1, ..Default::default() };
+        day.configure(&Options {
+            frames_dir: Some(dir.clone()),
+            frame_scale: Some(2),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(day.part1(&parse(EXAMPLE).unwrap()), 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 5);
+        let first = std::fs::read(dir.join("frame_000000.ppm")).unwrap();
+        // 7 wide plus walls, 4 rows for the rock and the gap plus the floor
+        assert!(first.starts_with(b"P6\n18 10\n255\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rocks_option() {
+        let mut day = Day17::default();
+        day.configure(&Options { rocks: Some("##\n##\n".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(day.rocks, parse_rocks("##\n##").unwrap());
+
+        let mut day = Day17::default();
+        let rocks = "###\n\n#####\n\n######\n";
+        let err = day.configure(&Options { rocks: Some(rocks.to_string()), ..Default::default() }).unwrap_err();
+        assert_eq!((err.line, err.column), (5, 1));
+        assert_eq!(err.expected, "a rock at most 5 wide");
+        assert_eq!(err.found, "a rock 6 wide");
+
+        let err = day.configure(&Options { rocks: Some("#x\n".to_string()), ..Default::default() }).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 2));
     }
 }
@@ -1,28 +1,7 @@
 
 use crate::parse_utils::*;
-use crate::arr_nd::ArrayND;
-
-
-fn add_coords(a: &[isize; 3], b: &[isize; 3]) -> [isize; 3] {
-    [a[0]+b[0], a[1]+b[1], a[2]+b[2]]
-}
-
-fn inside(coord: &[isize; 3], size: &[isize; 3]) -> bool {
-    for i in 0..3 {
-        if coord[i] < 0 { return false; }
-        if coord[i] >= size[i] { return false; }
-    }
-    return true;
-}
-
-const NEIGHBOURS: [[isize;3]; 6] = [
-    [-1,  0,  0],
-    [ 1,  0,  0],
-    [ 0, -1,  0],
-    [ 0,  1,  0],
-    [ 0,  0, -1],
-    [ 0,  0,  1]
-];
+use crate::arr_nd::{ArrayND, face_offsets};
+use crate::solution::Solution;
 
 
 fn multi_max<T: Ord+Copy, const N: usize>(mut a: [T; N], b: &[T;N]) -> [T;N] {
@@ -32,18 +11,16 @@ fn multi_max<T: Ord+Copy, const N: usize>(mut a: [T; N], b: &[T;N]) -> [T;N] {
     a
 }
 
-fn parse(source: &str) -> ArrayND<bool,3> {
-    let mut coords = Vec::new();
-    for line in source.split("\n") {
-        if line == "" { continue; }
-        let mut it = get_byte_iterator(line);
-        let a = parse_number(line, &mut it);
-        assert!(consume_sequence(&mut it, ","));
-        let b = parse_number(line, &mut it);
-        assert!(consume_sequence(&mut it, ","));
-        let c = parse_number(line, &mut it);
-        coords.push([a,b,c]);
-    }
+fn parse(source: &str) -> Result<ArrayND<bool,3>, ParseError> {
+    // The droplet is stored from the origin, so coordinates can't be negative
+    let coords = parse_lines(source, |src, it| {
+        let a = parse_unsigned(src, it)?;
+        expect_sequence(src, it, ",")?;
+        let b = parse_unsigned(src, it)?;
+        expect_sequence(src, it, ",")?;
+        let c = parse_unsigned(src, it)?;
+        Ok([a,b,c])
+    })?;
 
     let mut size = coords.iter().fold([0,0,0], multi_max);
     for s in &mut size { *s+=1; }
@@ -51,88 +28,65 @@ fn parse(source: &str) -> ArrayND<bool,3> {
     for coord in coords {
         *output.get_mut(&coord) = true;
     }
-    output
+    Ok(output)
 }
 
-fn flood_fill_holes(
-        start: [isize; 3],
-        blocks: &ArrayND<bool,3>,
-        new_blocks: &mut ArrayND<bool,3>)
-{
-    if !inside(&start, &blocks.size_i()) { return; }
-
-    // Don't fill if the original has a block there
-    // or if the new one already has it tagged as empty
-    if *blocks.get(&start) || !new_blocks.get(&start) { return; }
+/// Fills in any air that can't be reached from outside the droplet
+fn remove_pockets(blocks: &ArrayND<bool,3>) -> ArrayND<bool,3> {
+    let size = blocks.size_i();
 
-    *new_blocks.get_mut(&start) = false;
+    // Fill from every bit of air on the boundary at once
+    let starts = blocks.iter()
+        .filter(|(coord, block)| {
+            !**block && (0..3).any(|d| coord[d] == 0 || coord[d] == size[d]-1)
+        })
+        .map(|(coord, _)| coord);
+    let outside = blocks.flood_fill(starts, &face_offsets::<3>(), |block| !block);
 
-    for n in &NEIGHBOURS {
-        flood_fill_holes(add_coords(&start, n), blocks, new_blocks);
-    }
-}
-
-fn remove_pockets(blocks: ArrayND<bool,3>) -> ArrayND<bool,3> {
     let mut output = ArrayND::newu(true, blocks.size_u());
-    
-    let size = blocks.size_i();
-    for i in 0..size[0] {
-        for j in 0..size[1] {
-            flood_fill_holes([i,j,0], &blocks, &mut output);
-            flood_fill_holes([i,j,size[2]-1], &blocks, &mut output);
-        }
-    }
-    for j in 0..size[0] {
-        for k in 0..size[1] {
-            flood_fill_holes([0,j,k], &blocks, &mut output);
-            flood_fill_holes([size[0]-1,j,k], &blocks, &mut output);
-        }
-    }
-    for i in 0..size[0] {
-        for k in 0..size[1] {
-            flood_fill_holes([i,0,k], &blocks, &mut output);
-            flood_fill_holes([i,size[1]-1,k], &blocks, &mut output);
+    for (coord, is_outside) in outside.iter() {
+        if *is_outside {
+            *output.get_mut(&coord) = false;
         }
     }
     output
 }
 
 
-fn solve(input: &str, extern_only: bool) -> usize {
-    let blocks = parse(input);
-    let blocks = if extern_only { remove_pockets(blocks) } else { blocks };
+/// Counts faces of blocks that aren't against another block
+fn solve(blocks: &ArrayND<bool,3>, extern_only: bool) -> usize {
+    let blocks = if extern_only { remove_pockets(blocks) } else { blocks.clone() };
 
-    let size = blocks.size_i();
     let mut faces = 0;
-    for i in 0..size[0] {
-        for j in 0..size[1] {
-            for k in 0..size[2] {
-                let coord = [i,j,k];
-                if *blocks.get(&coord) {
-                    for n in &NEIGHBOURS {
-                        let ind = add_coords(&coord, n);
-                        if inside(&ind, &size) {
-                            if !blocks.get(&ind) {
-                                faces += 1;
-                            }
-                        } else {
-                            faces += 1;
-                        }
-                    }
-                }
-            }
+    for (coord, block) in blocks.iter() {
+        if *block {
+            let covered = blocks.face_neighbours(&coord).filter(|n| *blocks.get(n)).count();
+            faces += 6 - covered;
         }
     }
     faces
 }
 
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d18.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input, false));
-    println!("{}", solve(&input, true));
+#[derive(Default)]
+pub struct Day18;
+
+impl Solution for Day18 {
+    type Input = ArrayND<bool,3>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<ArrayND<bool,3>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, blocks: &ArrayND<bool,3>) -> usize {
+        solve(blocks, false)
+    }
+
+    fn part2(&self, blocks: &ArrayND<bool,3>) -> usize {
+        solve(blocks, true)
+    }
 }
 
 
@@ -159,17 +113,38 @@ mod tests {
 
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE11, false), 6);
-        assert_eq!(solve(EXAMPLE12, false), 0);
-        assert_eq!(solve(EXAMPLE21, false), 10);
-        assert_eq!(solve(EXAMPLE22, false), 64);
+        assert_eq!(solve(&parse(EXAMPLE11).unwrap(), false), 6);
+        assert_eq!(solve(&parse(EXAMPLE12).unwrap(), false), 0);
+        assert_eq!(solve(&parse(EXAMPLE21).unwrap(), false), 10);
+        assert_eq!(solve(&parse(EXAMPLE22).unwrap(), false), 64);
     }
     
     #[test]
     fn test_example_p2() {
-        assert_eq!(solve(EXAMPLE11, true), 6);
-        assert_eq!(solve(EXAMPLE12, true), 0);
-        assert_eq!(solve(EXAMPLE21, true), 10);
-        assert_eq!(solve(EXAMPLE22, true), 58);
+        assert_eq!(solve(&parse(EXAMPLE11).unwrap(), true), 6);
+        assert_eq!(solve(&parse(EXAMPLE12).unwrap(), true), 0);
+        assert_eq!(solve(&parse(EXAMPLE21).unwrap(), true), 10);
+        assert_eq!(solve(&parse(EXAMPLE22).unwrap(), true), 58);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("1,1,1\n2,-1,1").err().unwrap();
+        assert_eq!((err.line, err.column), (2, 3));
+    }
+
+    #[test]
+    fn test_large_droplet() {
+        // A hollow cube 100 across, only its outside counts
+        let n = 100;
+        let mut src = String::new();
+        for a in 0..n {
+            for b in 0..n {
+                for (x, y, z) in [(a, b, 0), (a, b, n-1), (a, 0, b), (a, n-1, b), (0, a, b), (n-1, a, b)] {
+                    src += &format!("{},{},{}\n", x, y, z);
+                }
+            }
+        }
+        assert_eq!(solve(&parse(&src).unwrap(), true), 6*n*n);
     }
 }
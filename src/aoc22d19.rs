@@ -0,0 +1,289 @@
+
+use crate::log;
+use crate::parse_utils::*;
+use crate::solution::Solution;
+
+const ORE: usize = 0;
+const CLAY: usize = 1;
+const OBSIDIAN: usize = 2;
+const GEODE: usize = 3;
+
+/// Resources, and the robots that collect them, in the order above
+const RESOURCES: [(&str, usize); 4] =
+    [("ore", ORE), ("clay", CLAY), ("obsidian", OBSIDIAN), ("geode", GEODE)];
+
+type Count = usize;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Blueprint {
+    id: Count,
+    /// costs[robot][resource]
+    costs: [[Count; 4]; 4],
+}
+
+/// Parses "3 ore" or similar
+fn parse_cost(src: &str, it: &mut ByteIterator) -> Result<(Count, usize), ParseError> {
+    consume_white_space(it);
+    let amount = parse_unsigned(src, it)?;
+    consume_white_space(it);
+    let resource = one_of(src, it, &RESOURCES)?;
+    Ok((amount, resource))
+}
+
+/// Parses a blueprint, which may be on one line or wrapped over several
+///
+/// All whitespace between words is treated the same.
+fn parse_blueprint(src: &str, it: &mut ByteIterator) -> Result<Blueprint, ParseError> {
+    expect_sequence(src, it, "Blueprint")?;
+    consume_white_space(it);
+    let id = parse_unsigned(src, it)?;
+    expect_sequence(src, it, ":")?;
+
+    let mut costs = [[0; 4]; 4];
+    let mut costed = [false; 4];
+    for _ in 0..RESOURCES.len() {
+        consume_white_space(it);
+        expect_sequence(src, it, "Each")?;
+        consume_white_space(it);
+        let robot_at = match it.peek() {
+            Some((index, _)) => *index,
+            None => src.len(),
+        };
+        let robot = one_of(src, it, &RESOURCES)?;
+        if costed[robot] {
+            return Err(ParseError::new(
+                src, robot_at, "a robot not already costed", &format!("{} again", RESOURCES[robot].0)));
+        }
+        costed[robot] = true;
+        consume_white_space(it);
+        expect_sequence(src, it, "robot")?;
+        consume_white_space(it);
+        expect_sequence(src, it, "costs")?;
+        let mut cost = parse_cost(src, it)?;
+        loop {
+            costs[robot][cost.1] += cost.0;
+            consume_white_space(it);
+            if !try_consume_sequence(src, it, "and")? {
+                break;
+            }
+            cost = parse_cost(src, it)?;
+        }
+        expect_sequence(src, it, ".")?;
+    }
+    Ok(Blueprint { id, costs })
+}
+
+fn parse(source: &str) -> Result<Vec<Blueprint>, ParseError> {
+    let mut it = get_byte_iterator(source);
+    let mut output = Vec::new();
+    loop {
+        consume_white_space(&mut it);
+        if it.peek().is_none() {
+            return Ok(output);
+        }
+        output.push(parse_blueprint(source, &mut it)?);
+    }
+}
+
+
+#[derive(Clone,Copy,Debug)]
+struct State {
+    time_left: Count,
+    robots: [Count; 4],
+    resources: [Count; 4],
+}
+
+/// Most geodes that could be opened from state, if a geode robot could be
+/// built every minute from now on
+fn upper_bound(state: &State) -> Count {
+    let t = state.time_left;
+    state.resources[GEODE] + state.robots[GEODE]*t + t*t.saturating_sub(1)/2
+}
+
+/// Minutes until robot can be built from what's being collected, if ever
+fn time_to_afford(state: &State, cost: &[Count; 4]) -> Option<Count> {
+    let mut wait = 0;
+    for (resource, &amount) in cost.iter().enumerate().take(GEODE) {
+        if amount <= state.resources[resource] {
+            continue;
+        }
+        if state.robots[resource] == 0 {
+            return None;
+        }
+        let needed = amount - state.resources[resource];
+        let robots = state.robots[resource];
+        wait = std::cmp::max(wait, needed.div_ceil(robots));
+    }
+    Some(wait)
+}
+
+/// Depth first search choosing which robot to build next
+///
+/// Rather than stepping a minute at a time, each choice skips straight to
+/// when the robot is built. There's no point having more of a robot than
+/// the most of its resource any robot costs, as that can be spent at most
+/// once a minute.
+fn search(blueprint: &Blueprint, max_costs: &[Count; 4], state: State, best: &mut Count) {
+    // Doing nothing more gets this many
+    let idle = state.resources[GEODE] + state.robots[GEODE]*state.time_left;
+    *best = std::cmp::max(*best, idle);
+    if upper_bound(&state) <= *best {
+        return;
+    }
+
+    // Geodes first, to find good answers early
+    for robot in (0..4).rev() {
+        if robot != GEODE && state.robots[robot] >= max_costs[robot] {
+            continue;
+        }
+        let cost = &blueprint.costs[robot];
+        let wait = match time_to_afford(&state, cost) {
+            Some(v) => v,
+            None => { continue; },
+        };
+        // A robot built in the last minute doesn't collect anything
+        if wait + 1 >= state.time_left {
+            continue;
+        }
+
+        let elapsed = wait + 1;
+        let mut next = state;
+        next.time_left -= elapsed;
+        for (resource, amount) in next.resources.iter_mut().enumerate() {
+            *amount += state.robots[resource]*elapsed;
+            *amount -= cost[resource];
+        }
+        next.robots[robot] += 1;
+        search(blueprint, max_costs, next, best);
+    }
+}
+
+/// Most geodes that can be opened in minutes
+fn max_geodes(blueprint: &Blueprint, minutes: Count) -> Count {
+    let mut max_costs = [0; 4];
+    for cost in blueprint.costs.iter() {
+        for (max_cost, amount) in max_costs.iter_mut().zip(cost) {
+            *max_cost = std::cmp::max(*max_cost, *amount);
+        }
+    }
+
+    let start = State {
+        time_left: minutes,
+        robots: [1, 0, 0, 0],
+        resources: [0; 4],
+    };
+    let mut best = 0;
+    search(blueprint, &max_costs, start, &mut best);
+    log::debug!("Blueprint {}: {} geodes", blueprint.id, best);
+    best
+}
+
+fn solvep1(blueprints: &[Blueprint], minutes: Count) -> Count {
+    blueprints.iter().map(|b| b.id*max_geodes(b, minutes)).sum()
+}
+
+fn solvep2(blueprints: &[Blueprint], minutes: Count, n_blueprints: usize) -> Count {
+    blueprints.iter().take(n_blueprints).map(|b| max_geodes(b, minutes)).product()
+}
+
+
+pub struct Day19 {
+    /// Minutes for the quality levels
+    pub minutes_p1: Count,
+    /// Minutes once the elephants have eaten most of the blueprints
+    pub minutes_p2: Count,
+    /// Blueprints that survived the elephants
+    pub blueprints_p2: usize,
+}
+
+impl Default for Day19 {
+    fn default() -> Day19 {
+        Day19 {
+            minutes_p1: 24,
+            minutes_p2: 32,
+            blueprints_p2: 3,
+        }
+    }
+}
+
+impl Solution for Day19 {
+    type Input = Vec<Blueprint>;
+    type Answer1 = Count;
+    type Answer2 = Count;
+
+    fn parse(&self, source: &str) -> Result<Vec<Blueprint>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, blueprints: &Vec<Blueprint>) -> Count {
+        solvep1(blueprints, self.minutes_p1)
+    }
+
+    fn part2(&self, blueprints: &Vec<Blueprint>) -> Count {
+        solvep2(blueprints, self.minutes_p2, self.blueprints_p2)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "
+Blueprint 1:
+  Each ore robot costs 4 ore.
+  Each clay robot costs 2 ore.
+  Each obsidian robot costs 3 ore and 14 clay.
+  Each geode robot costs 2 ore and 7 obsidian.
+
+Blueprint 2:
+  Each ore robot costs 2 ore.
+  Each clay robot costs 3 ore.
+  Each obsidian robot costs 3 ore and 8 clay.
+  Each geode robot costs 3 ore and 12 obsidian.
+";
+
+    #[test]
+    fn test_parse() {
+        let blueprints = parse(EXAMPLE).unwrap();
+        assert_eq!(blueprints.len(), 2);
+        assert_eq!(blueprints[1], Blueprint {
+            id: 2,
+            costs: [[2, 0, 0, 0], [3, 0, 0, 0], [3, 8, 0, 0], [3, 0, 12, 0]],
+        });
+
+        // The same on single lines
+        let single: Vec<String> = EXAMPLE.split("\n\n")
+            .map(|b| b.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(parse(&single.join("\n")).unwrap(), blueprints);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("Blueprint 1: Each ore robot costs 4 ore. Each ore robot costs 2 ore.")
+            .unwrap_err();
+        assert_eq!((err.line, err.column), (1, 47));
+        assert_eq!(err.expected, "a robot not already costed");
+
+        let err = parse("Blueprint 1: Each ore robot costs 4 gold.").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 37));
+        assert_eq!(err.expected, "one of \"ore\", \"clay\", \"obsidian\", \"geode\"");
+    }
+
+    #[test]
+    fn test_example() {
+        let blueprints = parse(EXAMPLE).unwrap();
+        assert_eq!(max_geodes(&blueprints[0], 24), 9);
+        assert_eq!(max_geodes(&blueprints[1], 24), 12);
+        assert_eq!(solvep1(&blueprints, 24), 33);
+    }
+
+    #[test]
+    fn test_example_p2() {
+        let blueprints = parse(EXAMPLE).unwrap();
+        assert_eq!(max_geodes(&blueprints[0], 32), 56);
+        assert_eq!(max_geodes(&blueprints[1], 32), 62);
+        assert_eq!(solvep2(&blueprints, 32, 3), 56*62);
+    }
+}
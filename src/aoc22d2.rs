@@ -1,20 +1,20 @@
 
 
+use crate::solution::Solution;
+use crate::parse_utils::*;
 
-#[derive(Clone,Copy,Debug)]
-enum Play {
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Play {
     Rock, Paper, Scissors
 }
 use Play::*;
 
 
+/// Plays in the order of val_of
+const PLAYS: [Play; 3] = [Rock, Paper, Scissors];
+
 fn play_from_val(a: i32) -> Play {
-    match a {
-        0 => Rock,
-        1 => Paper,
-        2 => Scissors,
-        _ => { panic!("Bad play"); }
-    }
+    PLAYS[a.rem_euclid(3) as usize]
 }
 
 fn val_of(a: Play) -> i32 {
@@ -26,12 +26,8 @@ fn val_of(a: Play) -> i32 {
 }
 
 fn score_of_game(other: Play, me: Play) -> i32 {
-    match (val_of(me) - val_of(other)+3)%3 {
-        0 => 3,
-        1 => 6,
-        2 => 0,
-        _ => { panic!("Who puts a bloody remainder operator in a language, really?!"); }
-    }
+    // Draw, win, lose
+    [3, 6, 0][(val_of(me) - val_of(other)).rem_euclid(3) as usize]
 }
 
 fn score_of_play(a: Play) -> i32 {
@@ -43,55 +39,58 @@ fn score_of_play(a: Play) -> i32 {
 }
 
 
-fn parse_play(line: usize, s: &str) -> Play {
-    match s {
-        "A" => Rock,
-        "B" => Paper,
-        "C" => Scissors,
-        "X" => Rock,
-        "Y" => Paper,
-        "Z" => Scissors,
-        c => { panic!("Invalid play: {} (line {})", c, line); }
-    }
+/// The second column is read as a play (X, Y, Z for rock, paper, scissors),
+/// part 2 reinterprets it as an outcome
+fn parse_round(src: &str, it: &mut ByteIterator) -> Result<(Play, Play), ParseError> {
+    let other = one_of(src, it, &[("A", Rock), ("B", Paper), ("C", Scissors)])?;
+    expect_sequence(src, it, " ")?;
+    let me = one_of(src, it, &[("X", Rock), ("Y", Paper), ("Z", Scissors)])?;
+    Ok((other, me))
 }
 
-fn parse_outcome(line: usize, s: &str) -> i32 {
-    match s {
-        "X" => -1,
-        "Y" =>  0,
-        "Z" =>  1,
-        c => { panic!("Invalid play: {} (line {})", c, line); }
-    }
+/// X, Y, Z mean lose, draw, win
+fn outcome_of(a: Play) -> i32 {
+    val_of(a) - 1
 }
 
 
-fn resolve_game_pt1(line: usize, s: &str) -> i32 {
-    let other = parse_play(line, &s[0..1]);
-    let me = parse_play(line, &s[2..3]);
+fn resolve_game_pt1((other, me): (Play, Play)) -> i32 {
     score_of_play(me) + score_of_game(other, me)
 }
 
-fn resolve_game_pt2(line: usize, s: &str) -> i32 {
-    let other = parse_play(line, &s[0..1]);
-    let outcome = parse_outcome(line, &s[2..3]);
-    let me = play_from_val((val_of(other) + outcome +3) % 3);
+fn resolve_game_pt2((other, second): (Play, Play)) -> i32 {
+    let me = play_from_val(val_of(other) + outcome_of(second));
 
     score_of_play(me) + score_of_game(other, me)
 }
 
-pub fn run() {
-    // 9407 is incorrect, too low :/
-    let src = std::fs::read_to_string("data/d2_actual.txt").expect("Failed to read input");
+/// Total score over every round in the strategy guide
+fn resolve_games(rounds: &[(Play, Play)], resolve_game: fn((Play, Play)) -> i32) -> i32 {
+    rounds.iter().map(|round| resolve_game(*round)).sum()
+}
+
+#[derive(Default)]
+pub struct Day2;
 
-    let mut score = 0;
-    let mut line_count = 0;
-    for line in src.split("\n") {
-        if line.len() == 0 { continue; }
-        line_count += 1;
-        score += resolve_game_pt2(line_count, line);
+impl Solution for Day2 {
+    type Input = Vec<(Play, Play)>;
+    type Answer1 = i32;
+    type Answer2 = i32;
+
+    fn parse(&self, source: &str) -> Result<Vec<(Play, Play)>, ParseError> {
+        parse_lines(source, parse_round)
+    }
+
+    /// Second column is what to play
+    fn part1(&self, rounds: &Vec<(Play, Play)>) -> i32 {
+        resolve_games(rounds, resolve_game_pt1)
     }
 
-    println!("Strategy results in {} points after {} rounds", score, line_count);
+    /// Second column is the outcome we want
+    fn part2(&self, rounds: &Vec<(Play, Play)>) -> i32 {
+        // 9407 is incorrect, too low :/
+        resolve_games(rounds, resolve_game_pt2)
+    }
 }
 
 
@@ -109,16 +108,27 @@ mod tests {
 
     #[test]
     fn test_resolve_game() {
-        assert_eq!(resolve_game_pt1(0, "A Y"), 8);
-        assert_eq!(resolve_game_pt1(0, "B X"), 1);
-        assert_eq!(resolve_game_pt1(0, "C Z"), 6);
+        assert_eq!(resolve_game_pt1((Rock, Paper)), 8);
+        assert_eq!(resolve_game_pt1((Paper, Rock)), 1);
+        assert_eq!(resolve_game_pt1((Scissors, Scissors)), 6);
     }
     
     #[test]
     fn test_resolve_game2() {
-        assert_eq!(resolve_game_pt2(0, "A Y"), 4);
-        assert_eq!(resolve_game_pt2(0, "B X"), 1);
-        assert_eq!(resolve_game_pt2(0, "C Z"), 7);
+        assert_eq!(resolve_game_pt2((Rock, Paper)), 4);
+        assert_eq!(resolve_game_pt2((Paper, Rock)), 1);
+        assert_eq!(resolve_game_pt2((Scissors, Scissors)), 7);
+    }
+
+    #[test]
+    fn test_parse() {
+        let rounds = Day2.parse("A Y\nB X\nC Z\n").unwrap();
+        assert_eq!(rounds, vec![(Rock, Paper), (Paper, Rock), (Scissors, Scissors)]);
+        assert_eq!(Day2.part1(&rounds), 15);
+        assert_eq!(Day2.part2(&rounds), 12);
+
+        let err = Day2.parse("A Y\nB W\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
     }
 }
 
@@ -1,23 +1,47 @@
 
+use crate::log;
 use crate::parse_utils::*;
 //use crate::arr2d::Array2d;
+use crate::solution::Solution;
 
 
-fn parse(source: &str) -> Vec<isize> {
+const DECRYPTION_KEY: isize = 811589153;
+
+/// Parses the file, which needs at least two numbers to mix and exactly one 0
+/// to find the coordinates from
+fn parse(source: &str) -> Result<Vec<isize>, ParseError> {
     let mut it = get_byte_iterator(source);
     let mut output = Vec::new();
+    let mut zero_found = false;
     consume_white_space(&mut it);
     loop {
-        match it.peek() {
-            Some(_) => {
-                output.push(parse_number(source, &mut it));
-            },
-            None => {
-                return output;
+        let start = match it.peek() {
+            Some((index, _)) => *index,
+            None => { break; },
+        };
+        let val = try_parse_number(source, &mut it)?;
+        // Small enough that three of them decrypted still add up
+        if val.checked_mul(3 * DECRYPTION_KEY).is_none() {
+            return Err(ParseError::new(
+                source, start, "a number small enough to decrypt", &val.to_string()));
+        }
+        if val == 0 {
+            if zero_found {
+                return Err(ParseError::new(source, start, "only one 0", "a second 0"));
             }
+            zero_found = true;
         }
+        output.push(val);
         consume_white_space(&mut it);
     }
+    if output.len() < 2 {
+        return Err(ParseError::new(
+            source, source.len(), "at least two numbers", &format!("{} numbers", output.len())));
+    }
+    if !zero_found {
+        return Err(ParseError::new(source, source.len(), "a 0", "end of input"));
+    }
+    Ok(output)
 }
 
 
@@ -25,7 +49,7 @@ fn mult(input: &Vec<isize>) -> Vec<isize> {
     let mut output = Vec::with_capacity(input.len());
 
     for v in input {
-        output.push(*v * 811589153);
+        output.push(*v * DECRYPTION_KEY);
     }
     output
 }
@@ -145,36 +169,48 @@ fn mix(input: &Vec<isize>, n: usize) -> Vec<isize> {
     output
 }
 
-fn solve(input: &str) -> isize {
-    let file = parse(input);
-    let new = mix(&file, 1);
+fn solve(file: &Vec<isize>) -> isize {
+    let new = mix(file, 1);
     let zero_index = find(0, &new);
     let a = new[(zero_index+1000) % new.len()];
     let b = new[(zero_index+2000) % new.len()];
     let c = new[(zero_index+3000) % new.len()];
-    println!("{} {} {}", a,b,c);
+    log::debug!("{} {} {}", a,b,c);
     a+b+c
 }
 
 
-fn solve2(input: &str) -> isize {
-    let file = mix(&mult(&parse(input)), 10);
+fn solve2(file: &Vec<isize>) -> isize {
+    let file = mix(&mult(file), 10);
 
     let zero_index = find(0, &file);
     let a = file[(zero_index+1000) % file.len()];
     let b = file[(zero_index+2000) % file.len()];
     let c = file[(zero_index+3000) % file.len()];
-    println!("{} {} {}", a,b,c);
+    log::debug!("{} {} {}", a,b,c);
     a+b+c
 }
 
 
-pub fn run() {
-    let input =
-        std::fs::read_to_string("data/d20.txt")
-        .expect("Failed to read input");
-    println!("{}", solve(&input));
-    println!("{}", solve2(&input));
+#[derive(Default)]
+pub struct Day20;
+
+impl Solution for Day20 {
+    type Input = Vec<isize>;
+    type Answer1 = isize;
+    type Answer2 = isize;
+
+    fn parse(&self, source: &str) -> Result<Vec<isize>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, file: &Vec<isize>) -> isize {
+        solve(file)
+    }
+
+    fn part2(&self, file: &Vec<isize>) -> isize {
+        solve2(file)
+    }
 }
 
 
@@ -192,14 +228,28 @@ mod tests {
     
     #[test]
     fn test_example() {
-        assert_eq!(solve(EXAMPLE), 3);
+        assert_eq!(solve(&parse(EXAMPLE).unwrap()), 3);
     }
     
     #[test]
     fn test_example2() {
         assert_eq!(
-            mult(&parse(EXAMPLE)),
+            mult(&parse(EXAMPLE).unwrap()),
             vec![811589153, 1623178306, -2434767459, 2434767459, -1623178306, 0, 3246356612]);
-        assert_eq!(solve2(EXAMPLE), 1623178306);
+        assert_eq!(solve2(&parse(EXAMPLE).unwrap()), 1623178306);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("1\n2\n").unwrap_err();
+        assert_eq!(err.expected, "a 0");
+        let err = parse("0\n").unwrap_err();
+        assert_eq!(err.expected, "at least two numbers");
+        let err = parse("").unwrap_err();
+        assert_eq!(err.expected, "at least two numbers");
+        let err = parse("0\n1\n0\n").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+        let err = parse("0\n-9000000000000000\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
     }
 }
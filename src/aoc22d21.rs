@@ -0,0 +1,377 @@
+
+use std::collections::{HashMap, HashSet};
+
+use crate::parse_utils::*;
+use crate::solution::Solution;
+
+type Value = i64;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum Job {
+    Number(Value),
+    Operation(String, Operator, String),
+}
+
+/// Every monkey's job by name
+pub type Monkeys = HashMap<String, Job>;
+
+const ROOT: &str = "root";
+const HUMAN: &str = "humn";
+
+/// Parses a monkey name, returning where it was for errors too
+fn parse_name<'a>(src: &'a str, it: &mut ByteIterator) -> Result<(usize, &'a str), ParseError> {
+    let start = match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    };
+    Ok((start, identifier(src, it)?))
+}
+
+/// Monkeys a job waits on, with where their names were for errors
+type WaitsOn<'a> = Vec<(usize, &'a str)>;
+
+/// Parses a job, along with the monkeys it waits on
+fn parse_job<'a>(src: &'a str, it: &mut ByteIterator) -> Result<(Job, WaitsOn<'a>), ParseError> {
+    match it.peek() {
+        Some((_, byte)) if is_digit(*byte) || *byte == b'-' => {
+            return Ok((Job::Number(parse_signed(src, it)?), Vec::new()));
+        },
+        _ => { },
+    }
+    let a = parse_name(src, it)?;
+    let operator = one_of(src, it, &[
+        (" + ", Operator::Add),
+        (" - ", Operator::Sub),
+        (" * ", Operator::Mul),
+        (" / ", Operator::Div)])?;
+    let b = parse_name(src, it)?;
+    Ok((Job::Operation(a.1.to_string(), operator, b.1.to_string()), vec![a, b]))
+}
+
+/// A monkey's name, where it was, and its job
+type Line<'a> = ((usize, &'a str), (Job, WaitsOn<'a>));
+
+/// Depth first search of who waits on whom, failing at the first name that
+/// leads back to a monkey still being searched from
+///
+/// Uses an explicit stack, as a long chain of monkeys could overflow the real
+/// one.
+fn check_no_cycles(source: &str, lines: &[Line]) -> Result<(), ParseError> {
+    let waits: HashMap<&str, &WaitsOn> = lines.iter()
+        .map(|((_, name), (_, waits_on))| (*name, waits_on))
+        .collect();
+    let mut searching = HashSet::new();
+    let mut done = HashSet::new();
+    for ((_, name), _) in lines {
+        if done.contains(name) {
+            continue;
+        }
+        // Each monkey with how many of the monkeys it waits on have been visited
+        let mut stack = vec![(*name, 0)];
+        searching.insert(*name);
+        while let Some(&(current, next)) = stack.last() {
+            let waits_on = waits[current];
+            if next == waits_on.len() {
+                searching.remove(current);
+                done.insert(current);
+                stack.pop();
+                continue;
+            }
+            let top = stack.len() - 1;
+            stack[top].1 += 1;
+            let (start, other) = waits_on[next];
+            if searching.contains(other) {
+                let expected = format!("a monkey that isn't waiting on {}", current);
+                return Err(ParseError::new(source, start, &expected, other));
+            }
+            if !done.contains(other) {
+                searching.insert(other);
+                stack.push((other, 0));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse(source: &str) -> Result<Monkeys, ParseError> {
+    let lines = parse_lines(source, |src, it| {
+        let name = parse_name(src, it)?;
+        expect_sequence(src, it, ": ")?;
+        Ok((name, parse_job(src, it)?))
+    })?;
+
+    let mut monkeys = HashMap::new();
+    for ((start, name), (job, _)) in lines.iter() {
+        if monkeys.insert(name.to_string(), job.clone()).is_some() {
+            return Err(ParseError::new(source, *start, "a new monkey", name));
+        }
+    }
+
+    // Check every monkey waited on exists, and that none ends up waiting on
+    // itself, so solving can't get stuck
+    for (_, (_, waits_on)) in lines.iter() {
+        for (start, other) in waits_on {
+            if !monkeys.contains_key(*other) {
+                return Err(ParseError::new(source, *start, "a known monkey", other));
+            }
+        }
+    }
+    check_no_cycles(source, &lines)?;
+    let root = match lines.iter().find(|((_, name), _)| *name == ROOT) {
+        Some(((start, _), _)) => *start,
+        None => {
+            return Err(ParseError::new(source, source.len(), "a monkey called root", "end of input"));
+        }
+    };
+
+    // Both parts need an answer, so check there is one for each
+    if evaluate(&monkeys, ROOT).is_none() {
+        return Err(ParseError::new(
+            source, root, "numbers that can be worked out", "a division by zero or overflow"));
+    }
+    if let Err(found) = find_human(&monkeys) {
+        let expected = format!("a whole number for {} to yell", HUMAN);
+        return Err(ParseError::new(source, root, &expected, &found));
+    }
+    Ok(monkeys)
+}
+
+fn apply(operator: Operator, a: Value, b: Value) -> Option<Value> {
+    match operator {
+        Operator::Add => a.checked_add(b),
+        Operator::Sub => a.checked_sub(b),
+        Operator::Mul => a.checked_mul(b),
+        Operator::Div => a.checked_div(b),
+    }
+}
+
+/// What the named monkey yells, None if working it out divides by zero or
+/// overflows
+fn evaluate(monkeys: &Monkeys, name: &str) -> Option<Value> {
+    match &monkeys[name] {
+        Job::Number(v) => Some(*v),
+        Job::Operation(a, operator, b) => {
+            apply(*operator, evaluate(monkeys, a)?, evaluate(monkeys, b)?)
+        },
+    }
+}
+
+/// Does the named monkey's number depend on what we yell?
+fn depends_on_human(monkeys: &Monkeys, name: &str) -> bool {
+    if name == HUMAN {
+        return true;
+    }
+    match &monkeys[name] {
+        Job::Number(_) => false,
+        Job::Operation(a, _, b) => depends_on_human(monkeys, a) || depends_on_human(monkeys, b),
+    }
+}
+
+/// Exact x with x / b == target, if there's a whole number one
+fn undo_div_left(target: Value, b: Value) -> Option<Value> {
+    if b == 0 { None } else { target.checked_mul(b) }
+}
+
+/// Exact x with a / x == target, if there's a whole number one
+fn undo_div_right(a: Value, target: Value) -> Option<Value> {
+    if target == 0 {
+        return None;
+    }
+    let x = a / target;
+    if x != 0 && a / x == target { Some(x) } else { None }
+}
+
+/// Exact x with x * b == target, if there's a whole number one
+fn undo_mul(target: Value, b: Value) -> Option<Value> {
+    if b != 0 && target % b == 0 { Some(target / b) } else { None }
+}
+
+/// What we need to yell for the named monkey to yell target
+///
+/// Only one side of each operation may depend on us, so work out the other
+/// side and undo the operation, down to us. Errors say what got in the way.
+fn solve_for_human(monkeys: &Monkeys, name: &str, target: Value) -> Result<Value, String> {
+    if name == HUMAN {
+        return Ok(target);
+    }
+    let (a, operator, b) = match &monkeys[name] {
+        Job::Number(_) => { return Err(format!("{} not depending on {}", name, HUMAN)); },
+        Job::Operation(a, operator, b) => (a, *operator, b),
+    };
+    let known = |other: &str| {
+        evaluate(monkeys, other).ok_or(format!("{} dividing by zero or overflowing", other))
+    };
+    let (unknown, x) = match (depends_on_human(monkeys, a), depends_on_human(monkeys, b)) {
+        (true, true) => { return Err(format!("{} on both sides of {}", HUMAN, name)); },
+        (true, false) => {
+            // target = x op b
+            let b = known(b)?;
+            (a, match operator {
+                Operator::Add => target.checked_sub(b),
+                Operator::Sub => target.checked_add(b),
+                Operator::Mul => undo_mul(target, b),
+                Operator::Div => undo_div_left(target, b),
+            })
+        },
+        _ => {
+            // target = a op x
+            let a = known(a)?;
+            (b, match operator {
+                Operator::Add => target.checked_sub(a),
+                Operator::Sub => a.checked_sub(target),
+                Operator::Mul => undo_mul(target, a),
+                Operator::Div => undo_div_right(a, target),
+            })
+        },
+    };
+    match x {
+        Some(x) => solve_for_human(monkeys, unknown, x),
+        None => Err(format!("no whole number for {} that makes {} yell {}", unknown, name, target)),
+    }
+}
+
+/// What we need to yell for root's two numbers to be equal
+fn find_human(monkeys: &Monkeys) -> Result<Value, String> {
+    if !monkeys.contains_key(HUMAN) {
+        return Err(format!("no monkey called {}", HUMAN));
+    }
+    let (a, b) = match &monkeys[ROOT] {
+        Job::Number(_) => { return Err(format!("{} yelling a number", ROOT)); },
+        Job::Operation(a, _, b) => (a, b),
+    };
+    let (unknown, known) = match (depends_on_human(monkeys, a), depends_on_human(monkeys, b)) {
+        (true, true) => { return Err(format!("{} on both sides of {}", HUMAN, ROOT)); },
+        (false, false) => { return Err(format!("{} not depending on {}", ROOT, HUMAN)); },
+        (true, false) => (a, b),
+        (false, true) => (b, a),
+    };
+    let target = evaluate(monkeys, known)
+        .ok_or(format!("{} dividing by zero or overflowing", known))?;
+    let human = solve_for_human(monkeys, unknown, target)?;
+
+    // Undoing each step exactly should balance, but check
+    let mut check = monkeys.clone();
+    check.insert(HUMAN.to_string(), Job::Number(human));
+    if evaluate(&check, unknown) != Some(target) {
+        return Err(format!("{} yelling {} not balancing {}", HUMAN, human, ROOT));
+    }
+    Ok(human)
+}
+
+fn solvep1(monkeys: &Monkeys) -> Value {
+    evaluate(monkeys, ROOT).expect("Checked when parsing")
+}
+
+fn solvep2(monkeys: &Monkeys) -> Value {
+    find_human(monkeys).expect("Checked when parsing")
+}
+
+
+#[derive(Default)]
+pub struct Day21;
+
+impl Solution for Day21 {
+    type Input = Monkeys;
+    type Answer1 = Value;
+    type Answer2 = Value;
+
+    fn parse(&self, source: &str) -> Result<Monkeys, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, monkeys: &Monkeys) -> Value {
+        solvep1(monkeys)
+    }
+
+    fn part2(&self, monkeys: &Monkeys) -> Value {
+        solvep2(monkeys)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "root: pppw + sjmn
+dbpl: 5
+cczh: sllz + lgvd
+zczc: 2
+ptdq: humn - dvpt
+dvpt: 3
+lfqf: 4
+humn: 5
+ljgn: 2
+sjmn: drzm * dbpl
+sllz: 4
+pppw: cczh / lfqf
+lgvd: ljgn * ptdq
+drzm: hmdt - zczc
+hmdt: 32";
+
+    #[test]
+    fn test_example() {
+        let monkeys = parse(EXAMPLE).unwrap();
+        assert_eq!(evaluate(&monkeys, "sjmn"), Some(150));
+        assert_eq!(solvep1(&monkeys), 152);
+    }
+
+    #[test]
+    fn test_example_p2() {
+        assert_eq!(solvep2(&parse(EXAMPLE).unwrap()), 301);
+    }
+
+    #[test]
+    fn test_human_on_right() {
+        // 100 - 12 / humn == 96
+        let source = "root: nine + ten
+ten: hund - quot
+nine: 96
+hund: 100
+quot: twlv / humn
+twlv: 12
+humn: 7";
+        assert_eq!(solvep2(&parse(source).unwrap()), 3);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("root: aaaa + bbbb\naaaa: 3").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 14));
+        assert_eq!(err.expected, "a known monkey");
+
+        let err = parse("root: aaaa % bbbb").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 11));
+
+        // 2 * humn can't be 7
+        let err = parse("humn: 1\nroot: dble + svn\ndble: two * humn\ntwo: 2\nsvn: 7").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.expected, "a whole number for humn to yell");
+        assert_eq!(err.found, "no whole number for humn that makes dble yell 7");
+
+        let err = parse("root: aaaa + humn\naaaa: humn * humn\nhumn: 3").unwrap_err();
+        assert_eq!(err.found, "humn on both sides of root");
+
+        let err = parse("root: aaaa + humn\naaaa: zero / zero\nzero: 0\nhumn: 3").unwrap_err();
+        assert_eq!(err.expected, "numbers that can be worked out");
+    }
+
+    #[test]
+    fn test_cycle() {
+        let err = parse("root: aaaa + humn\naaaa: bbbb + humn\nbbbb: aaaa + humn\nhumn: 3").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 7));
+        assert_eq!(err.expected, "a monkey that isn't waiting on bbbb");
+        assert_eq!(err.found, "aaaa");
+
+        let err = parse("root: root + humn\nhumn: 3").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 7));
+    }
+}
@@ -0,0 +1,397 @@
+
+use std::collections::HashMap;
+
+use crate::arr2d::Array2d;
+use crate::parse_utils::*;
+use crate::solution::Solution;
+
+// Coordinates are (column, row), both from 0 at the top left
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Tile {
+    /// Off the map
+    Void,
+    Open,
+    Wall,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Step {
+    Forward(usize),
+    Left,
+    Right,
+}
+
+#[derive(Debug)]
+pub struct Notes {
+    map: Array2d<Tile>,
+    path: Vec<Step>,
+    /// The map folded up, for part 2
+    cube: Cube,
+}
+
+/// Facing right, down, left and up, in the order the password scores them
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+fn parse(source: &str) -> Result<Notes, ParseError> {
+    let mut it = get_byte_iterator(source);
+
+    // Map rows are ragged, so find the widest before building the map
+    let mut rows = Vec::new();
+    loop {
+        let start = match it.peek() {
+            Some((index, _)) => *index,
+            None => source.len(),
+        };
+        let line = take_while(source, &mut it, |b| b != b'\n');
+        if line.is_empty() {
+            break;
+        }
+        rows.push((start, line));
+        expect_line_end(source, &mut it)?;
+    }
+    if rows.is_empty() {
+        return Err(error_at(source, &mut it, "a map"));
+    }
+    expect_line_end(source, &mut it)?;
+
+    let width = rows.iter().map(|(_, line)| line.len()).max().unwrap();
+    let mut map = Array2d::newu(Tile::Void, width, rows.len());
+    for (y, (start, line)) in rows.iter().enumerate() {
+        for (x, c) in line.bytes().enumerate() {
+            *map.get_mutu((x, y)) = match c {
+                b' ' => Tile::Void,
+                b'.' => Tile::Open,
+                b'#' => Tile::Wall,
+                _ => {
+                    return Err(ParseError::new(
+                        source, start + x, "' ', '.' or '#'", &format!("'{}'", c as char)));
+                }
+            };
+        }
+    }
+
+    let mut path = Vec::new();
+    loop {
+        match it.peek() {
+            None | Some((_, b'\n')) => { break; },
+            Some((_, byte)) if is_digit(*byte) => {
+                path.push(Step::Forward(parse_unsigned(source, &mut it)?));
+            },
+            _ => {
+                path.push(one_of(source, &mut it, &[("L", Step::Left), ("R", Step::Right)])?);
+            }
+        }
+    }
+    while try_consume_sequence(source, &mut it, "\n")? { }
+    expect_end(source, &mut it)?;
+
+    if !map.row_coords(0).any(|c| *map.get(c) == Tile::Open) {
+        return Err(ParseError::new(source, 0, "an open tile on the top row", rows[0].1));
+    }
+    let cube = match Cube::fold(&map) {
+        Ok(cube) => cube,
+        Err(found) => {
+            return Err(ParseError::new(source, 0, "a map that folds into a cube", &found));
+        }
+    };
+    Ok(Notes { map, path, cube })
+}
+
+
+fn tile_at(map: &Array2d<Tile>, pos: (isize, isize)) -> Tile {
+    match map.try_get(pos) {
+        Some(tile) => *tile,
+        None => Tile::Void,
+    }
+}
+
+/// Follows the path, wrap says where stepping off the map from a position
+/// and facing leads (and the facing after). Returns the password.
+fn walk(notes: &Notes, wrap: impl Fn((isize, isize), usize) -> ((isize, isize), usize)) -> isize {
+    let start = notes.map.row_coords(0).find(|c| *notes.map.get(*c) == Tile::Open);
+    let mut pos = start.expect("Checked when parsing");
+    let mut facing = 0;
+
+    for step in notes.path.iter() {
+        match step {
+            Step::Left => { facing = (facing + 3) % 4; },
+            Step::Right => { facing = (facing + 1) % 4; },
+            Step::Forward(n) => {
+                for _ in 0..*n {
+                    let (dx, dy) = DIRECTIONS[facing];
+                    let mut next = ((pos.0 + dx, pos.1 + dy), facing);
+                    if tile_at(&notes.map, next.0) == Tile::Void {
+                        next = wrap(pos, facing);
+                    }
+                    if tile_at(&notes.map, next.0) == Tile::Wall {
+                        break;
+                    }
+                    (pos, facing) = next;
+                }
+            },
+        }
+    }
+    1000*(pos.1 + 1) + 4*(pos.0 + 1) + facing as isize
+}
+
+/// Wraps to the far side of the map, keeping the same facing
+fn flat_wrap(map: &Array2d<Tile>, pos: (isize, isize), facing: usize) -> ((isize, isize), usize) {
+    let (dx, dy) = DIRECTIONS[facing];
+    let mut pos = pos;
+    while tile_at(map, (pos.0 - dx, pos.1 - dy)) != Tile::Void {
+        pos = (pos.0 - dx, pos.1 - dy);
+    }
+    (pos, facing)
+}
+
+
+type Vec3 = [isize; 3];
+
+fn neg(a: Vec3) -> Vec3 {
+    [-a[0], -a[1], -a[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> isize {
+    a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
+}
+
+/// A face of the cube, where it is on the map and which way it points
+///
+/// Moving right on the map moves along `right` in 3D, moving down along
+/// `down`, and `normal` points out of the cube.
+#[derive(Clone,Copy,Debug)]
+struct Face {
+    origin: (isize, isize),
+    right: Vec3,
+    down: Vec3,
+    normal: Vec3,
+}
+
+/// The map folded up into a cube
+#[derive(Debug)]
+struct Cube {
+    size: isize,
+    faces: Vec<Face>,
+    /// Face index by (column, row) in units of faces
+    face_at: HashMap<(isize, isize), usize>,
+}
+
+impl Cube {
+    /// Folds any net of a cube, or says what's wrong with the map
+    ///
+    /// Starting from one face, each face next to one already placed on the
+    /// map is folded over the edge between them.
+    fn fold(map: &Array2d<Tile>) -> Result<Cube, String> {
+        let area = map.coords().filter(|c| *map.get(*c) != Tile::Void).count() as isize;
+        let size = (1..).find(|s| 6*s*s >= area).unwrap();
+        if 6*size*size != area {
+            return Err(format!("{} tiles, which isn't six square faces", area));
+        }
+
+        let (width, height) = map.size_i();
+        let is_face = |f: (isize, isize)| {
+            0 <= f.0 && f.0 < width/size && 0 <= f.1 && f.1 < height/size
+                && *map.get((f.0*size, f.1*size)) != Tile::Void
+        };
+        let not_faces = || "tiles that aren't six square faces".to_string();
+        let first = (0..width/size).map(|fx| (fx, 0)).find(|f| is_face(*f)).ok_or_else(not_faces)?;
+
+        let mut cube = Cube { size, faces: Vec::new(), face_at: HashMap::new() };
+        let mut to_visit = vec![(first, Face {
+            origin: (first.0*size, first.1*size),
+            right: [1, 0, 0],
+            down: [0, 1, 0],
+            normal: [0, 0, 1],
+        })];
+        while let Some((f, face)) = to_visit.pop() {
+            if cube.face_at.contains_key(&f) {
+                continue;
+            }
+            cube.face_at.insert(f, cube.faces.len());
+            cube.faces.push(face);
+
+            // Folding over the right edge turns the old right into the new
+            // normal, and similarly for the others
+            let folds = [
+                ((1, 0), Face { right: neg(face.normal), normal: face.right, ..face }),
+                ((-1, 0), Face { right: face.normal, normal: neg(face.right), ..face }),
+                ((0, 1), Face { down: neg(face.normal), normal: face.down, ..face }),
+                ((0, -1), Face { down: face.normal, normal: neg(face.down), ..face }),
+            ];
+            for ((dx, dy), folded) in folds {
+                let next = (f.0 + dx, f.1 + dy);
+                if is_face(next) {
+                    to_visit.push((next, Face { origin: (next.0*size, next.1*size), ..folded }));
+                }
+            }
+        }
+        if cube.faces.len() != 6 {
+            return Err(not_faces());
+        }
+        // With six faces all there, there can't be any tiles off them
+        for face in cube.faces.iter() {
+            let (x, y) = face.origin;
+            let filled = |j| (0..size).all(|i| *map.get((x + i, y + j)) != Tile::Void);
+            if !(0..size).all(filled) {
+                return Err(not_faces());
+            }
+        }
+        for (i, face) in cube.faces.iter().enumerate() {
+            if cube.faces[..i].iter().any(|f| f.normal == face.normal) {
+                return Err("faces that overlap when folded".to_string());
+            }
+        }
+        Ok(cube)
+    }
+
+    /// Which way facing points in 3D on face
+    fn direction(face: &Face, facing: usize) -> Vec3 {
+        [face.right, face.down, neg(face.right), neg(face.down)][facing]
+    }
+
+    /// Rolls over the edge of the cube onto the next face
+    ///
+    /// Positions are the centres of tiles in 3D, doubled to keep them whole,
+    /// with the cube centred on the origin.
+    fn wrap(&self, pos: (isize, isize), facing: usize) -> ((isize, isize), usize) {
+        let n = self.size;
+        let from = &self.faces[self.face_at[&(pos.0/n, pos.1/n)]];
+        let (i, j) = (pos.0 - from.origin.0, pos.1 - from.origin.1);
+        let direction = Cube::direction(from, facing);
+        let mut point = [0; 3];
+        for k in 0..3 {
+            point[k] = n*from.normal[k] + (2*i - (n-1))*from.right[k] + (2*j - (n-1))*from.down[k]
+                + direction[k] - from.normal[k];
+        }
+
+        let to = self.faces.iter().find(|f| f.normal == direction).unwrap();
+        let i = (dot(point, to.right) + n - 1)/2;
+        let j = (dot(point, to.down) + n - 1)/2;
+        let facing = (0..4).find(|d| Cube::direction(to, *d) == neg(from.normal)).unwrap();
+        ((to.origin.0 + i, to.origin.1 + j), facing)
+    }
+}
+
+
+fn solvep1(notes: &Notes) -> isize {
+    walk(notes, |pos, facing| flat_wrap(&notes.map, pos, facing))
+}
+
+fn solvep2(notes: &Notes) -> isize {
+    walk(notes, |pos, facing| notes.cube.wrap(pos, facing))
+}
+
+
+#[derive(Default)]
+pub struct Day22;
+
+impl Solution for Day22 {
+    type Input = Notes;
+    type Answer1 = isize;
+    type Answer2 = isize;
+
+    fn parse(&self, source: &str) -> Result<Notes, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, notes: &Notes) -> isize {
+        solvep1(notes)
+    }
+
+    fn part2(&self, notes: &Notes) -> isize {
+        solvep2(notes)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "        ...#
+        .#..
+        #...
+        ....
+...#.......#
+........#...
+..#....#....
+..........#.
+        ...#....
+        .....#..
+        .#......
+        ......#.
+
+10R5L5R10L4R5L5
+";
+
+    #[test]
+    fn test_parse() {
+        let notes = parse(EXAMPLE).unwrap();
+        assert_eq!(notes.map.size_i(), (16, 12));
+        assert_eq!(*notes.map.get((0, 0)), Tile::Void);
+        assert_eq!(*notes.map.get((11, 0)), Tile::Wall);
+        assert_eq!(notes.path.len(), 13);
+        assert_eq!(notes.path[0..3], [Step::Forward(10), Step::Right, Step::Forward(5)]);
+
+        let err = parse("..\n.x\n\n1R").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+        let err = parse("..\n..\n\n1X").unwrap_err();
+        assert_eq!((err.line, err.column), (4, 2));
+    }
+
+    #[test]
+    fn test_not_a_cube() {
+        let err = parse("..\n..\n\n1R").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+        assert_eq!(err.expected, "a map that folds into a cube");
+        assert_eq!(err.found, "4 tiles, which isn't six square faces");
+
+        // Six faces, but in a row they wrap round onto each other
+        let err = parse("......\n\n1R").unwrap_err();
+        assert_eq!(err.found, "faces that overlap when folded");
+
+        // Six 2x2 faces worth of tiles, but not lined up as faces
+        let err = parse(" ....\n ....\n......\n......\n  ..\n  ..\n\n1").unwrap_err();
+        assert_eq!(err.found, "tiles that aren't six square faces");
+
+        let err = parse("#.\n\n1").unwrap_err();
+        assert_eq!(err.expected, "a map that folds into a cube");
+        let err = parse("##\n..\n\n1").unwrap_err();
+        assert_eq!(err.expected, "an open tile on the top row");
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(solvep1(&parse(EXAMPLE).unwrap()), 6032);
+    }
+
+    #[test]
+    fn test_example_p2() {
+        assert_eq!(solvep2(&parse(EXAMPLE).unwrap()), 5031);
+    }
+
+    #[test]
+    fn test_cube_wrap() {
+        let notes = parse(EXAMPLE).unwrap();
+        let cube = &notes.cube;
+        // The examples from the puzzle text
+        assert_eq!(cube.wrap((11, 5), 0), ((14, 8), 1));
+        assert_eq!(cube.wrap((10, 11), 1), ((1, 7), 3));
+        assert_eq!(cube.wrap((6, 4), 3), ((8, 2), 0));
+
+        // Going off every edge and straight back gets back to the start
+        for (pos, tile) in notes.map.coords().map(|c| (c, *notes.map.get(c))) {
+            if tile == Tile::Void {
+                continue;
+            }
+            for (facing, (dx, dy)) in DIRECTIONS.into_iter().enumerate() {
+                if tile_at(&notes.map, (pos.0 + dx, pos.1 + dy)) != Tile::Void {
+                    continue;
+                }
+                let (there, arrived) = cube.wrap(pos, facing);
+                assert_eq!(cube.wrap(there, (arrived + 2) % 4), (pos, (facing + 2) % 4));
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+
+use crate::arr2d::Array2d;
+use crate::offset_grid::OffsetGrid;
+use crate::parse_utils::ParseError;
+use crate::solution::Solution;
+
+// Coordinates are (column, row), with rows increasing southwards
+
+/// Where the elves are, both as a grid to look around and as a list
+struct Grove {
+    occupied: OffsetGrid<bool>,
+    elves: Vec<(isize, isize)>,
+}
+
+impl Grove {
+    fn new(elves: &[(isize, isize)]) -> Grove {
+        let mut occupied = OffsetGrid::new(false);
+        for elf in elves {
+            *occupied.get_mut(*elf) = true;
+        }
+        Grove { occupied, elves: elves.to_vec() }
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<(isize, isize)>, ParseError> {
+    let grove = Array2d::try_from_text(source, |c| match c {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    })?;
+    Ok(grove.coords().filter(|c| *grove.get(*c)).collect())
+}
+
+type Offset = (isize, isize);
+
+/// Directions elves consider, as the move and the three cells to check
+const PROPOSALS: [(Offset, [Offset; 3]); 4] = [
+    // North
+    ((0, -1), [(-1, -1), (0, -1), (1, -1)]),
+    // South
+    ((0, 1), [(-1, 1), (0, 1), (1, 1)]),
+    // West
+    ((-1, 0), [(-1, -1), (-1, 0), (-1, 1)]),
+    // East
+    ((1, 0), [(1, -1), (1, 0), (1, 1)]),
+];
+
+/// Where an elf wants to move, if anywhere
+fn propose(grove: &Grove, (x, y): (isize, isize), round: usize) -> Option<(isize, isize)> {
+    let occupied = |(dx, dy): Offset| *grove.occupied.get((x + dx, y + dy));
+    let crowded = (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .any(|d| d != (0, 0) && occupied(d));
+    if !crowded {
+        return None;
+    }
+    for k in 0..PROPOSALS.len() {
+        let ((dx, dy), checks) = PROPOSALS[(round + k) % PROPOSALS.len()];
+        if !checks.iter().any(|d| occupied(*d)) {
+            return Some((x + dx, y + dy));
+        }
+    }
+    None
+}
+
+/// Runs one round, returns whether any elf moved
+fn step(grove: &mut Grove, round: usize) -> bool {
+    let proposals: Vec<_> = grove.elves.iter()
+        .map(|elf| propose(grove, *elf, round))
+        .collect();
+
+    let mut counts = OffsetGrid::new(0);
+    for to in proposals.iter().flatten() {
+        *counts.get_mut(*to) += 1;
+    }
+
+    let mut moved = false;
+    for (elf, proposal) in grove.elves.iter_mut().zip(proposals) {
+        // Elves that would bump into each other stay put
+        if let Some(to) = proposal {
+            if *counts.get(to) == 1 {
+                *grove.occupied.get_mut(*elf) = false;
+                *grove.occupied.get_mut(to) = true;
+                *elf = to;
+                moved = true;
+            }
+        }
+    }
+    moved
+}
+
+/// Empty ground in the smallest rectangle containing every elf
+fn empty_ground(grove: &Grove) -> usize {
+    match grove.occupied.used_bounds() {
+        Some(((xmin, ymin), (xmax, ymax))) => {
+            ((xmax - xmin)*(ymax - ymin)) as usize - grove.elves.len()
+        },
+        None => 0,
+    }
+}
+
+fn solvep1(start: &[(isize, isize)], rounds: usize) -> usize {
+    let mut grove = Grove::new(start);
+    for round in 0..rounds {
+        step(&mut grove, round);
+    }
+    empty_ground(&grove)
+}
+
+fn solvep2(start: &[(isize, isize)]) -> usize {
+    let mut grove = Grove::new(start);
+    let mut round = 0;
+    while step(&mut grove, round) {
+        round += 1;
+    }
+    round + 1
+}
+
+
+pub struct Day23 {
+    pub rounds_p1: usize,
+}
+
+impl Default for Day23 {
+    fn default() -> Day23 {
+        Day23 {
+            rounds_p1: 10,
+        }
+    }
+}
+
+impl Solution for Day23 {
+    type Input = Vec<(isize, isize)>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Vec<(isize, isize)>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, elves: &Vec<(isize, isize)>) -> usize {
+        solvep1(elves, self.rounds_p1)
+    }
+
+    fn part2(&self, elves: &Vec<(isize, isize)>) -> usize {
+        solvep2(elves)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "....#..
+..###.#
+#...#.#
+.#...##
+#.###..
+##.#.##
+.#..#..";
+
+    #[test]
+    fn test_small() {
+        let source = ".....
+..##.
+..#..
+.....
+..##.
+.....";
+        let mut grove = Grove::new(&parse(source).unwrap());
+        let mut round = 0;
+        while step(&mut grove, round) {
+            round += 1;
+        }
+        grove.elves.sort_by_key(|(x, y)| (*y, *x));
+        assert_eq!(grove.elves, vec![(2, 0), (4, 1), (0, 2), (4, 3), (2, 5)]);
+        assert_eq!(round, 3);
+        assert_eq!(empty_ground(&grove), 5*6 - 5);
+    }
+
+    #[test]
+    fn test_example() {
+        let elves = parse(EXAMPLE).unwrap();
+        assert_eq!(elves.len(), 22);
+        assert_eq!(solvep1(&elves, 10), 110);
+    }
+
+    #[test]
+    fn test_example_p2() {
+        assert_eq!(solvep2(&parse(EXAMPLE).unwrap()), 20);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("..#\n.o.").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+    }
+}
@@ -0,0 +1,219 @@
+
+use crate::arr2d::Array2d;
+use crate::parse_utils::ParseError;
+use crate::search;
+use crate::solution::Solution;
+
+// Coordinates are (column, row) on the whole map, walls included
+
+#[derive(Debug)]
+pub struct Valley {
+    map: Array2d<char>,
+    start: (isize, isize),
+    end: (isize, isize),
+}
+
+/// The one gap in a wall row
+fn find_gap(map: &Array2d<char>, row: isize) -> Option<(isize, isize)> {
+    let mut gaps = map.row_coords(row).filter(|c| *map.get(*c) == '.');
+    let gap = gaps.next();
+    match gaps.next() {
+        None => gap,
+        Some(_) => None,
+    }
+}
+
+/// Byte offset of a map coordinate in the source it was read from
+fn offset_of(source: &str, (x, y): (isize, isize)) -> usize {
+    source.split('\n').take(y as usize).map(|line| line.len() + 1).sum::<usize>() + x as usize
+}
+
+fn parse(source: &str) -> Result<Valley, ParseError> {
+    let map = Array2d::try_from_text(source, |c| match c {
+        '#' | '.' | '>' | '<' | '^' | 'v' => Some(c),
+        _ => None,
+    })?;
+    let (width, height) = map.size_i();
+    if width < 3 || height < 3 {
+        return Err(ParseError::new(
+            source, 0, "a valley with room inside its walls",
+            &format!("a map {} by {}", width, height)));
+    }
+    let start = find_gap(&map, 0);
+    let end = find_gap(&map, height - 1);
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => {
+            return Err(ParseError::new(
+                source, 0, "a valley with one gap in the top and bottom walls", "something else"));
+        }
+    };
+    let border = map.row_coords(0)
+        .chain(map.row_coords(height - 1))
+        .chain(map.col_coords(0))
+        .chain(map.col_coords(width - 1));
+    for pos in border {
+        if pos != start && pos != end && *map.get(pos) != '#' {
+            return Err(ParseError::new(
+                source, offset_of(source, pos), "a wall", &format!("'{}'", map.get(pos))));
+        }
+    }
+    let valley = Valley { map, start, end };
+
+    // Waiting at either end is always safe, so if we can cross each way from
+    // one minute we can from any
+    if valley.crossing(start, end, 0).is_none() || valley.crossing(end, start, 0).is_none() {
+        return Err(ParseError::new(
+            source, 0, "a valley with a way through", "blizzards blocking every way"));
+    }
+    Ok(valley)
+}
+
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl Valley {
+    /// Size of the area inside the walls
+    fn inner_size(&self) -> (isize, isize) {
+        let (width, height) = self.map.size_i();
+        (width - 2, height - 2)
+    }
+
+    /// Blizzards are all back where they started after this many minutes
+    fn period(&self) -> isize {
+        let (w, h) = self.inner_size();
+        w*h/gcd(w, h)
+    }
+
+    /// Is pos clear of walls and blizzards at minute t?
+    ///
+    /// Blizzards move in straight lines and wrap, so rather than moving
+    /// them look back along each line to where one would have started.
+    fn is_clear(&self, pos: (isize, isize), t: isize) -> bool {
+        if pos == self.start || pos == self.end {
+            return true;
+        }
+        let (w, h) = self.inner_size();
+        let (x, y) = (pos.0 - 1, pos.1 - 1);
+        if x < 0 || x >= w || y < 0 || y >= h {
+            return false;
+        }
+        let at = |x: isize, y: isize| *self.map.get((1 + x.rem_euclid(w), 1 + y.rem_euclid(h)));
+        at(x - t, y) != '>' && at(x + t, y) != '<' && at(x, y - t) != 'v' && at(x, y + t) != '^'
+    }
+
+    /// Earliest time we can get from `from` to `to` setting off at minute t
+    ///
+    /// Searches space and time together. Time only matters up to the
+    /// period of the blizzards, so that's all the nodes need to hold.
+    fn crossing(&self, from: (isize, isize), to: (isize, isize), t: isize) -> Option<isize> {
+        let period = self.period();
+        let result = search::bfs(
+            [(from, t % period)],
+            |((x, y), phase)| {
+                let next = (phase + 1) % period;
+                [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)].into_iter()
+                    .map(move |(dx, dy)| (x + dx, y + dy))
+                    .filter(move |p| self.is_clear(*p, next))
+                    .map(move |p| (p, next))
+            },
+            |(pos, _)| pos == to);
+        result.goal_distance().map(|d| t + d as isize)
+    }
+}
+
+fn solvep1(valley: &Valley) -> isize {
+    valley.crossing(valley.start, valley.end, 0).expect("Checked when parsing")
+}
+
+fn solvep2(valley: &Valley) -> isize {
+    let cross = |from, to, t| valley.crossing(from, to, t).expect("Checked when parsing");
+    let there = cross(valley.start, valley.end, 0);
+    let back = cross(valley.end, valley.start, there);
+    cross(valley.start, valley.end, back)
+}
+
+
+#[derive(Default)]
+pub struct Day24;
+
+impl Solution for Day24 {
+    type Input = Valley;
+    type Answer1 = isize;
+    type Answer2 = isize;
+
+    fn parse(&self, source: &str) -> Result<Valley, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, valley: &Valley) -> isize {
+        solvep1(valley)
+    }
+
+    fn part2(&self, valley: &Valley) -> isize {
+        solvep2(valley)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "#.######
+#>>.<^<#
+#.<..<<#
+#>v.><>#
+#<^v^^>#
+######.#";
+
+    #[test]
+    fn test_blizzards() {
+        let valley = parse(EXAMPLE).unwrap();
+        assert_eq!(valley.start, (1, 0));
+        assert_eq!(valley.end, (6, 5));
+        assert_eq!(valley.period(), 12);
+        // Minute 1 from the puzzle text, '.' is clear
+        let expected = [
+            "#.######",
+            "#.>3.<.#",
+            "#<..<<.#",
+            "#>2.22.#",
+            "#>v..^<#",
+            "######.#"];
+        for (y, row) in expected.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let pos = (x as isize, y as isize);
+                assert_eq!(valley.is_clear(pos, 1), c == '.', "at {:?}", pos);
+            }
+        }
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(solvep1(&parse(EXAMPLE).unwrap()), 18);
+    }
+
+    #[test]
+    fn test_example_p2() {
+        let valley = parse(EXAMPLE).unwrap();
+        assert_eq!(valley.crossing(valley.end, valley.start, 18), Some(18 + 23));
+        assert_eq!(solvep2(&valley), 54);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("#.##\n#.x#\n##.#").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert!(parse("#..#\n#..#\n##.#").is_err());
+
+        let err = parse("#.\n..\n.#").unwrap_err();
+        assert_eq!(err.expected, "a valley with room inside its walls");
+        let err = parse("#.##\n>..#\n##.#").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.expected, "a wall");
+        let err = parse("#.#\n#<#\n#.#").unwrap_err();
+        assert_eq!(err.expected, "a valley with a way through");
+    }
+}
@@ -0,0 +1,149 @@
+
+use crate::parse_utils::*;
+use crate::solution::Solution;
+
+/// SNAFU digits in order of value, from -2 to 2
+const DIGITS: [(&str, i64); 5] = [("=", -2), ("-", -1), ("0", 0), ("1", 1), ("2", 2)];
+
+/// Parses a balanced base five number
+fn parse_snafu(src: &str, it: &mut ByteIterator) -> Result<i64, ParseError> {
+    let start = match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    };
+    let mut value = one_of(src, it, &DIGITS)?;
+    while let Some((_, byte)) = it.peek() {
+        if *byte == b'\n' {
+            break;
+        }
+        let digit = one_of(src, it, &DIGITS)?;
+        value = match value.checked_mul(5).and_then(|v| v.checked_add(digit)) {
+            Some(v) => v,
+            None => {
+                return Err(ParseError::new(src, start, "a number that fits in 64 bits", "a bigger one"));
+            }
+        };
+    }
+    Ok(value)
+}
+
+fn to_snafu(value: i64) -> String {
+    if value == 0 {
+        return "0".to_string();
+    }
+    let mut digits = Vec::new();
+    let mut value = value;
+    while value != 0 {
+        // Digits 3 and 4 become -2 and -1 with a carry, worked out without
+        // going past the ends of i64
+        let remainder = value.rem_euclid(5);
+        let carry = if remainder > 2 { 1 } else { 0 };
+        digits.push(DIGITS[(remainder - 5*carry + 2) as usize].0);
+        value = value.div_euclid(5) + carry;
+    }
+    digits.reverse();
+    digits.concat()
+}
+
+/// Parses a number per line, checking they add up without overflowing
+fn parse(source: &str) -> Result<Vec<i64>, ParseError> {
+    let mut total: i64 = 0;
+    parse_lines(source, |src, it| {
+        let start = match it.peek() {
+            Some((index, _)) => *index,
+            None => src.len(),
+        };
+        let value = parse_snafu(src, it)?;
+        total = match total.checked_add(value) {
+            Some(t) => t,
+            None => {
+                return Err(ParseError::new(
+                    src, start, "numbers that add up to fit in 64 bits", "one that doesn't"));
+            }
+        };
+        Ok(value)
+    })
+}
+
+fn solvep1(numbers: &[i64]) -> String {
+    to_snafu(numbers.iter().sum())
+}
+
+
+#[derive(Default)]
+pub struct Day25;
+
+impl Solution for Day25 {
+    type Input = Vec<i64>;
+    type Answer1 = String;
+    // There's only one puzzle on the last day
+    type Answer2 = &'static str;
+
+    fn parse(&self, source: &str) -> Result<Vec<i64>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, numbers: &Vec<i64>) -> String {
+        solvep1(numbers)
+    }
+
+    fn part2(&self, _: &Vec<i64>) -> &'static str {
+        "no part 2 on day 25"
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "1=-0-2
+12111
+2=0=
+21
+2=01
+111
+20012
+112
+1=-1=
+1-12
+12
+1=
+122";
+
+    #[test]
+    fn test_round_trip() {
+        let table = [
+            (1, "1"), (2, "2"), (3, "1="), (4, "1-"), (5, "10"), (8, "2="), (10, "20"),
+            (15, "1=0"), (20, "1-0"), (2022, "1=11-2"), (12345, "1-0---0"),
+            (314159265, "1121-1110-1=0"), (0, "0"), (-3, "-2"),
+            (i64::MAX, "1110--=-02=100==0-0=11=11212")];
+        for (value, snafu) in table {
+            assert_eq!(to_snafu(value), snafu);
+            assert_eq!(parse(snafu).unwrap(), vec![value]);
+        }
+    }
+
+    #[test]
+    fn test_example() {
+        let numbers = parse(EXAMPLE).unwrap();
+        assert_eq!(numbers.iter().sum::<i64>(), 4890);
+        assert_eq!(solvep1(&numbers), "2=-1=0");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("1=\n1=3").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.expected, "one of \"=\", \"-\", \"0\", \"1\", \"2\"");
+
+        // Each fits, the total doesn't
+        let max = to_snafu(i64::MAX);
+        let err = parse(&format!("1\n{}1\n", max)).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.expected, "a number that fits in 64 bits");
+        let err = parse(&format!("1\n{}\n", max)).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.expected, "numbers that add up to fit in 64 bits");
+    }
+}
@@ -2,10 +2,13 @@
 
 
 
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
 const N_TYPES: usize = 52;
 
 #[derive(Clone,Copy,Debug)]
-struct Rucksack {
+pub struct Rucksack {
     comps: [[usize; N_TYPES]; 2],
 }
 
@@ -14,125 +17,127 @@ fn empty_rucksack() -> Rucksack {
 }
 
 
-fn read_rucksack(line: &str) -> Rucksack  {
+fn read_rucksack(src: &str, it: &mut ByteIterator) -> Result<Rucksack, ParseError> {
     let mut output = empty_rucksack();
 
+    let start = match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    };
+    let line = take_while(src, it, |b| b.is_ascii_alphabetic());
+    match it.peek() {
+        Some((_, b'\n')) | None => { },
+        Some(_) => {
+            return Err(error_at(src, it, "a letter or end of line"));
+        }
+    }
     if line.len() % 2 != 0 {
-        panic!("Odd number of characters on a line");
+        return Err(ParseError::new(
+            src, start, "an even number of items", &format!("{} items", line.len())));
     }
 //       abcdefghijklmnopqrstuvwxyz
     let comp_size = line.len() / 2;
-    let mut i = 0;
-    for c in line.bytes() {
+    for (i, c) in line.bytes().enumerate() {
         let index = 
-            if c < b'A' {
-                panic!("Unexpected character {}", c);
-            } else if c <= b'Z' {
+            if c <= b'Z' {
                 c - b'A' + (b'z'-b'a') + 1
-            } else if c < b'a' {
-                panic!("Unexpected character {}", c);
-            } else if c <= b'z' {
-                c - b'a'
             } else {
-                panic!("Unexpected character {}", c);
+                c - b'a'
             } as usize;
 
         let cindex = if i < comp_size { 0 } else { 1 };
 
         output.comps[cindex][index] += 1;
-        i += 1;
     }
-    output
+    Ok(output)
 }
 
 
-fn find_common_val(r: &Rucksack) -> usize {
-    let mut item_num = usize::MAX;
-    for i in 0..N_TYPES {
-        if r.comps[0][i] > 0 && r.comps[1][i] > 0 {
-            if item_num != usize::MAX {
-                panic!("Expected only one type to be common");
-            }
-            item_num = i;
-        }
+/// The only item type in both compartments, None if there isn't exactly one
+fn find_common_val(r: &Rucksack) -> Option<usize> {
+    let mut common = (0..N_TYPES).filter(|&i| r.comps[0][i] > 0 && r.comps[1][i] > 0);
+    match (common.next(), common.next()) {
+        (Some(i), None) => Some(i),
+        _ => None,
     }
-    item_num
 }
 
-fn get_next_non_empty_line<'a>(it: &'a mut std::str::Split<&str>) -> Option<&'a str> {
-    loop {
-        match it.next() {
-            Some(s) => {
-                if s != "" {
-                    return Some(s);
-                } else {
-                    continue;
-                }
-            },
-            None => { return None; }
+/// The only item type carried by every elf in the group, None if there isn't
+/// exactly one
+fn find_badge(group: &[Rucksack]) -> Option<usize> {
+    let mut common = (0..N_TYPES)
+        .filter(|&i| group.iter().all(|r| r.comps[0][i] > 0 || r.comps[1][i] > 0));
+    match (common.next(), common.next()) {
+        (Some(i), None) => Some(i),
+        _ => None,
+    }
+}
+
+/// Parses a rucksack per line, checking every rucksack has one item type in
+/// both compartments and every group of three has one badge
+fn parse_rucksacks(src: &str) -> Result<Vec<Rucksack>, ParseError> {
+    let mut starts = Vec::new();
+    let rucksacks = parse_lines(src, |src, it| {
+        let start = match it.peek() {
+            Some((index, _)) => *index,
+            None => src.len(),
+        };
+        let r = read_rucksack(src, it)?;
+        if find_common_val(&r).is_none() {
+            return Err(ParseError::new(
+                src, start, "a rucksack with one item type in both compartments", "a rucksack without"));
+        }
+        starts.push(start);
+        Ok(r)
+    })?;
+    if rucksacks.len() % 3 != 0 {
+        return Err(ParseError::new(
+            src, src.len(), "a multiple of three rucksacks",
+            &format!("{} rucksacks", rucksacks.len())));
+    }
+    for (group, start) in rucksacks.chunks(3).zip(starts.iter().step_by(3)) {
+        if find_badge(group).is_none() {
+            return Err(ParseError::new(
+                src, *start, "a group of three with one common item type", "a group without"));
         }
     }
+    Ok(rucksacks)
 }
 
 fn priority_from_val(i: usize) -> usize { i + 1 }
 
-fn solvep2(input: &str) -> usize {
-    let mut result = 0;
-
-    let mut it = input.split("\n");
-    loop {
-        let mut rucksacks = [empty_rucksack(); 3];
-        for i in 0..3 {
-            match get_next_non_empty_line(&mut it) {
-                Some(s) => { rucksacks[i] = read_rucksack(s); }
-                None => {
-                    if i == 0 {
-                        return result;
-                    } else {
-                        panic!("Unexpected parity of rucksacks (ended on {})", i);
-                    }
-                }
-            }
-        }
+fn solvep2(rucksacks: &[Rucksack]) -> usize {
+    rucksacks.chunks(3)
+        .map(|group| priority_from_val(find_badge(group).expect("Checked when parsing")))
+        .sum()
+}
 
-        let mut item_num = usize::MAX;
-        for i in 0..N_TYPES {
-            if
-                (rucksacks[0].comps[0][i]>0 || rucksacks[0].comps[1][i]>0) &&
-                (rucksacks[1].comps[0][i]>0 || rucksacks[1].comps[1][i]>0) &&
-                (rucksacks[2].comps[0][i]>0 || rucksacks[2].comps[1][i]>0)
-            {
-                if item_num != usize::MAX {
-                    panic!("Expected only one type to be common");
-                }
-                item_num = i;
-            }
-        }
-        if item_num == usize::MAX {
-            panic!("Failed to find common item\n{:?}", rucksacks);
-        }
-        let p = priority_from_val(item_num);
-        result += p;
-    }
+fn solvep1(rucksacks: &[Rucksack]) -> usize {
+    rucksacks.iter()
+        .map(|r| priority_from_val(find_common_val(r).expect("Checked when parsing")))
+        .sum()
 }
 
-fn solvep1(input: &str) -> usize {
-    let mut result = 0;
-    for line in input.split("\n") {
-        if line == "" { continue; }
-        let r = read_rucksack(line);
-        let c = find_common_val(&r);
-        let p = priority_from_val(c);
-        result += p;
+
+#[derive(Default)]
+pub struct Day3;
+
+impl Solution for Day3 {
+    type Input = Vec<Rucksack>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Vec<Rucksack>, ParseError> {
+        parse_rucksacks(source)
     }
-    result
-}
 
+    fn part1(&self, rucksacks: &Vec<Rucksack>) -> usize {
+        solvep1(rucksacks)
+    }
 
-pub fn run() {
-    let input = std::fs::read_to_string("data/d3.txt").expect("Failed to read input");
-    println!("Sum of priorities is {}", solvep1(&input));
-    println!("Sum of priorities for the badges is {}", solvep2(&input));
+    fn part2(&self, rucksacks: &Vec<Rucksack>) -> usize {
+        solvep2(rucksacks)
+    }
 }
 
 
@@ -142,7 +147,8 @@ mod tests {
     
     #[test]
     fn test_read() {
-        let r = read_rucksack("pq");
+        let src = "pq";
+        let r = read_rucksack(src, &mut get_byte_iterator(src)).unwrap();
         println!("{:?}", r.comps[0]);
         assert_eq!(r.comps[0][15], 1);
         assert_eq!(r.comps[1][16], 1);
@@ -157,7 +163,7 @@ PmmdzqPrVvPwwTWBwg
 wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
 ttgJtRGJQctTZtZT
 CrZsJsPPZsGzwwsLwLmpwMDw";
-        assert_eq!(solvep1(example_input), 157);
+        assert_eq!(solvep1(&parse_rucksacks(example_input).unwrap()), 157);
     }
     
     #[test]
@@ -169,6 +175,21 @@ PmmdzqPrVvPwwTWBwg
 wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
 ttgJtRGJQctTZtZT
 CrZsJsPPZsGzwwsLwLmpwMDw";
-        assert_eq!(solvep2(example_input), 70);
+        assert_eq!(solvep2(&parse_rucksacks(example_input).unwrap()), 70);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse_rucksacks("vJrwpWtwJgWrhcsFMMfFFhFp\njqHRNqRjqz1\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 11));
+        let err = parse_rucksacks("vJrwpWtwJgWrhcsFMMfFFhF\n").unwrap_err();
+        assert_eq!(err.expected, "an even number of items");
+        let err = parse_rucksacks("abcd\n").unwrap_err();
+        assert_eq!(err.expected, "a rucksack with one item type in both compartments");
+        let err = parse_rucksacks("abca\nabcb\n").unwrap_err();
+        assert_eq!(err.expected, "a multiple of three rucksacks");
+        let err = parse_rucksacks("abca\nabcb\nbcdb\n").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+        assert_eq!(err.expected, "a group of three with one common item type");
     }
 }
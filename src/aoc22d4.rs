@@ -1,32 +1,28 @@
 
-fn parse_range(string: &str) -> (usize, usize) {
-    let mut r_it = string.split("-");
-    let s =
-        r_it.next()
-        .expect("Missing first range value")
-        .parse()
-        .expect("Failed to parse first range_value");
-    let e =
-        r_it.next()
-        .expect("Missing second range value")
-        .parse()
-        .expect("Failed to parse second range_value");
-    (s,e)
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
+type Pair = (usize, usize, usize, usize);
+
+/// Parses "<start>-<end>"
+fn parse_range(src: &str, it: &mut ByteIterator) -> Result<(usize, usize), ParseError> {
+    let s = parse_unsigned(src, it)?;
+    expect_sequence(src, it, "-")?;
+    let e = parse_unsigned(src, it)?;
+    Ok((s,e))
 }
 
-fn parse_line(s: &str) -> (usize, usize, usize, usize) {
-    let mut ab = s.split(",");
-    let (a_s, a_e) = parse_range(ab.next().expect("Missing first range"));
-    let (b_s, b_e) = parse_range(ab.next().expect("Missing second range"));
-    (a_s, a_e, b_s, b_e)
+fn parse_line(src: &str, it: &mut ByteIterator) -> Result<Pair, ParseError> {
+    let (a_s, a_e) = parse_range(src, it)?;
+    expect_sequence(src, it, ",")?;
+    let (b_s, b_e) = parse_range(src, it)?;
+    Ok((a_s, a_e, b_s, b_e))
 }
 
 
-fn solvep1(input: &str) -> usize {
+fn solvep1(pairs: &[Pair]) -> usize {
     let mut result = 0;
-    for line in input.split("\n") {
-        if line == "" { continue; }
-        let (a_s, a_e, b_s, b_e) = parse_line(line);
+    for &(a_s, a_e, b_s, b_e) in pairs {
         if (a_s >= b_s && a_e <= b_e) || (a_s <= b_s && a_e >= b_e) {
             result += 1;
         }
@@ -34,11 +30,9 @@ fn solvep1(input: &str) -> usize {
     result
 }
 
-fn solvep2(input: &str) -> usize {
+fn solvep2(pairs: &[Pair]) -> usize {
     let mut result = 0;
-    for line in input.split("\n") {
-        if line == "" { continue; }
-        let (a_s, a_e, b_s, b_e) = parse_line(line);
+    for &(a_s, a_e, b_s, b_e) in pairs {
         // Ranges overlap if both starts are before both ends
         if a_s <= b_e && b_s <= a_e {
             result += 1;
@@ -47,10 +41,25 @@ fn solvep2(input: &str) -> usize {
     result
 }
 
-pub fn run() {
-    let input = std::fs::read_to_string("data/d4.txt").expect("Failed to read input");
-    println!("part1: {}", solvep1(&input));
-    println!("part2: {}", solvep2(&input));
+#[derive(Default)]
+pub struct Day4;
+
+impl Solution for Day4 {
+    type Input = Vec<Pair>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Vec<Pair>, ParseError> {
+        parse_lines(source, parse_line)
+    }
+
+    fn part1(&self, pairs: &Vec<Pair>) -> usize {
+        solvep1(pairs)
+    }
+
+    fn part2(&self, pairs: &Vec<Pair>) -> usize {
+        solvep2(pairs)
+    }
 }
 
 
@@ -67,11 +76,19 @@ mod tests {
 2-8,3-7
 6-6,4-6
 2-6,4-8";
-        assert_eq!(solvep1(example_input), 2);
-        assert_eq!(solvep2(example_input), 4);
+        let pairs = parse_lines(example_input, parse_line).unwrap();
+        assert_eq!(solvep1(&pairs), 2);
+        assert_eq!(solvep2(&pairs), 4);
     }
     
     #[test]
     fn test_example_p2() {
     }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse_lines("2-4,6-8\n2-3;4-5\n", parse_line).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 4));
+        assert_eq!(err.expected, "\",\"");
+    }
 }
@@ -1,18 +1,7 @@
 
-fn get_next_non_empty_line<'a>(it: &'a mut std::str::Split<&str>) -> Option<&'a str> {
-    loop {
-        match it.next() {
-            Some(s) => {
-                if s != "" {
-                    return Some(s);
-                } else {
-                    continue;
-                }
-            },
-            None => { return None; }
-        }
-    }
-}
+use crate::log;
+use crate::solution::Solution;
+use crate::parse_utils::*;
 
 type Stacks = Vec<Vec<char>>;
 struct Order {
@@ -21,164 +10,174 @@ struct Order {
     amount: usize,
 }
 
-fn count_box_stacks(line: &str) -> usize {
+/// A crate is "[X]", or three spaces where there's no crate
+fn parse_crate(src: &str, it: &mut ByteIterator) -> Result<Option<char>, ParseError> {
+    if try_consume_sequence(src, it, "   ")? {
+        return Ok(None);
+    }
+    expect_sequence(src, it, "[")?;
+    let label = match it.next() {
+        Some((_, byte)) if byte.is_ascii_alphabetic() => byte as char,
+        _ => { return Err(error_at(src, it, "a crate label")); }
+    };
+    expect_sequence(src, it, "]")?;
+    Ok(Some(label))
+}
+
+/// The stack labels must run 1, 2, 3, ..., returns how many there are
+fn parse_stack_labels(src: &str, it: &mut ByteIterator) -> Result<usize, ParseError> {
     let mut last_label = 0;
-    for label in line.split(" ") {
-        if label == "" { continue; }
-        let val = label.parse().expect(&format!("Failed to parse label '{}'", label));
+    loop {
+        spaces(it);
+        match it.peek() {
+            None | Some((_, b'\n')) => { break; },
+            _ => { },
+        }
+        let offset = it.peek().unwrap().0;
+        let val: usize = parse_unsigned(src, it)?;
         if val != last_label + 1 {
-            panic!("Labels were not sequential");
+            let expected = format!("stack label {}", last_label + 1);
+            return Err(ParseError::new(src, offset, &expected, &val.to_string()));
         }
         last_label = val;
     }
-    last_label
+    Ok(last_label)
 }
 
-fn parse_crate(s: &str) -> Option<char> {
-    let mut iter = s.chars();
-    match iter.next() {
-        Some(' ') => {
-            assert_eq!(iter.next().expect("Unexpect end of crate input"), ' ');
-            assert_eq!(iter.next().expect("Unexpect end of crate input"), ' ');
-            return None;
-        },
-        Some('[') => {
-            let val = iter.next();
-            assert_eq!(iter.next().expect("Unexpect end of crate input"), ']');
-            return val;
-        },
-        Some(_) => {
-            panic!("Unexpected character parsing crate");
-        }
-        None => {
-            panic!("Empty string given to parse_crate");
+/// Parses the drawing of the crates down to (and including) the label line
+fn parse_start(src: &str, it: &mut ByteIterator) -> Result<Stacks, ParseError> {
+    let mut rows = Vec::new();
+    loop {
+        let row = optional(src, it, |src, it| {
+            let row = separated(src, it, " ", parse_crate)?;
+            expect_line_end(src, it)?;
+            Ok(row)
+        });
+        match row {
+            Some(row) => { rows.push(row); },
+            None => { break; }
         }
     }
-}
-    
-fn parse_crate_stacks(lines: Vec<String>, stack_count: usize) -> Stacks {
+    let offset = match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    };
+    let stack_count = parse_stack_labels(src, it)?;
+    expect_line_end(src, it)?;
+
     let mut stacks: Stacks = vec![Vec::new(); stack_count];
-    for line in lines.iter().rev() {
-        let n_cols = (line.len() + 1)/4;
-        assert_eq!(n_cols*4-1, line.len(), "Unexpected line length parsing stacks");
-        assert!(n_cols <= stack_count, "Unexpectedly long line length parsing stacks");
-        for i in 0..n_cols {
-            match parse_crate(&line[(i*4)..(i*4+3)]) {
-                Some(krate) => { stacks[i].push(krate); },
-                None => { },
-            }
+    for row in rows.iter().rev() {
+        if row.len() > stack_count {
+            let expected = format!("at most {} stacks", stack_count);
+            return Err(ParseError::new(src, offset, &expected, &format!("{} stacks", row.len())));
         }
-    }
-    stacks
-}
-
-fn parse_start(it: &mut std::str::Split<&str>) -> Stacks {
-    let mut lines: Vec<String> = Vec::new();
-
-    let stack_count = loop {
-        match get_next_non_empty_line(it) {
-            Some(line) => {
-                if &line[0..4] == " 1  " {
-                    break count_box_stacks(line);
-                }
-                lines.push(line.to_string());
-            },
-            None => {
-                panic!("Unexpected end of input, didn't find stack label line");
-            }
+        for (i, krate) in row.iter().enumerate() {
+            if let Some(krate) = krate { stacks[i].push(*krate); }
         }
-    };
-
-    parse_crate_stacks(lines, stack_count)
+    }
+    Ok(stacks)
 }
 
-fn print_stacks(stacks: &Stacks) {
+fn format_stacks(stacks: &Stacks) -> String {
     let biggest_stack = stacks.iter().fold(0, |acc, x| std::cmp::max(x.len(), acc));
 
+    let mut output = String::new();
     for height in (0..biggest_stack).rev() {
         for stack in stacks {
             match stack.get(height) {
-                Some(val) => { print!("[{}] ", val); },
-                None => { print!("    "); },
+                Some(val) => { output += &format!("[{}] ", val); },
+                None => { output += "    "; },
             }
         }
-        print!("\n");
+        output += "\n";
     }
 
     for i in 0..stacks.len() {
-        print!(" {:<3}", i+1);
+        output += &format!(" {:<3}", i+1);
     }
-    print!("\n");
+    output
+}
+
+/// Parses a stack number, which counts from 1, and returns its index
+fn parse_stack_number(src: &str, it: &mut ByteIterator, n_stacks: usize)
+    -> Result<usize, ParseError>
+{
+    let start = match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    };
+    let number: usize = parse_unsigned(src, it)?;
+    if number == 0 || number > n_stacks {
+        return Err(ParseError::new(
+            src, start, &format!("a stack from 1 to {}", n_stacks), &number.to_string()));
+    }
+    Ok(number - 1)
+}
+
+fn parse_order(src: &str, it: &mut ByteIterator, n_stacks: usize) -> Result<Order, ParseError> {
+    expect_sequence(src, it, "move ")?;
+    let amt = parse_unsigned(src, it)?;
+    expect_sequence(src, it, " from ")?;
+    let from = parse_stack_number(src, it, n_stacks)?;
+    expect_sequence(src, it, " to ")?;
+    let to = parse_stack_number(src, it, n_stacks)?;
+    Ok(Order {
+        from,
+        to,
+        amount: amt,
+    })
 }
 
-fn parse_order(line: &str) -> Option<Order> {
-    if line == "" {
-        None
-    } else {
-        let mut it = line.split(" ");
-        assert_eq!(it.next().expect("Unexpected end of input reading order"), "move");
-        let amt = 
-            it.next()
-            .expect("Unexpected end of input reading order")
-            .parse()
-            .expect("Failed to parse amount");
-        assert_eq!(it.next().expect("Unexpected end of input reading order"), "from");
-        let from: usize = 
-            it.next()
-            .expect("Unexpected end of input reading order")
-            .parse()
-            .expect("Failed to parse from");
-        assert_eq!(it.next().expect("Unexpected end of input reading order"), "to");
-        let to: usize = 
-            it.next()
-            .expect("Unexpected end of input reading order")
-            .parse()
-            .expect("Failed to parse to");
-        Some(Order { 
-            from: from - 1,
-            to: to - 1,
-            amount: amt,
-        })
+/// The starting stacks, then the orders to rearrange them
+pub struct Procedure {
+    stacks: Stacks,
+    orders: Vec<Order>,
+}
+
+fn parse(source: &str) -> Result<Procedure, ParseError> {
+    let mut it = get_byte_iterator(source);
+    while try_consume_sequence(source, &mut it, "\n")? { }
+    let stacks = parse_start(source, &mut it)?;
+    let mut orders = Vec::new();
+    loop {
+        // Skip blank lines
+        while try_consume_sequence(source, &mut it, "\n")? { }
+        if it.peek().is_none() {
+            break;
+        }
+        orders.push(parse_order(source, &mut it, stacks.len())?);
+        expect_line_end(source, &mut it)?;
     }
+    Ok(Procedure { stacks, orders })
 }
 
 
-fn apply_order(order: Order, stacks: &mut Stacks) {
+fn apply_order(order: &Order, stacks: &mut Stacks) {
     for _ in 0..order.amount {
         match stacks[order.from].pop() {
             Some(krate) => {
                 stacks[order.to].push(krate);
             },
             None => {
-                println!(
-                    "Asked to move crate from {} to {} but stack is empty:",
+                panic!(
+                    "Asked to move crate from {} to {} but stack is empty:\n{}",
                     order.from + 1,
-                    order.to + 1);
-                print_stacks(stacks);
-                panic!("Stopping");
+                    order.to + 1,
+                    format_stacks(stacks));
             }
         }
     }
 }
 
-fn solvep1(input: &str) -> String {
-    let mut input_it = input.split("\n");
-    let mut stacks = parse_start(&mut input_it);
+fn solvep1(procedure: &Procedure) -> String {
+    let mut stacks = procedure.stacks.clone();
 
-    println!("----START----");
-    print_stacks(&stacks);
+    log::debug!("----START----\n{}", format_stacks(&stacks));
 
-    for order in input_it.map(|x| parse_order(x)) {
-        match order {
-            Some(order) => {
-                apply_order(order, &mut stacks);
-            },
-            None => {},
-        }
+    for order in &procedure.orders {
+        apply_order(order, &mut stacks);
     }
-    println!("---- END ----");
-    print_stacks(&stacks);
-    println!("----     ----");
+    log::debug!("---- END ----\n{}\n----     ----", format_stacks(&stacks));
     let mut output = "".to_string();
     for stack in stacks {
         output.push(stack[stack.len()-1]);
@@ -187,7 +186,7 @@ fn solvep1(input: &str) -> String {
 }
 
 
-fn apply_order_p2(order: Order, stacks: &mut Stacks) {
+fn apply_order_p2(order: &Order, stacks: &mut Stacks) {
     let from_size = stacks[order.from].len();
     for i in 0..order.amount {
         let from_index = from_size - order.amount + i;
@@ -200,24 +199,15 @@ fn apply_order_p2(order: Order, stacks: &mut Stacks) {
 }
 
 
-fn solvep2(input: &str) -> String {
-    let mut input_it = input.split("\n");
-    let mut stacks = parse_start(&mut input_it);
+fn solvep2(procedure: &Procedure) -> String {
+    let mut stacks = procedure.stacks.clone();
 
-    println!("----START----");
-    print_stacks(&stacks);
+    log::debug!("----START----\n{}", format_stacks(&stacks));
 
-    for order in input_it.map(|x| parse_order(x)) {
-        match order {
-            Some(order) => {
-                apply_order_p2(order, &mut stacks);
-            },
-            None => {},
-        }
+    for order in &procedure.orders {
+        apply_order_p2(order, &mut stacks);
     }
-    println!("---- END ----");
-    print_stacks(&stacks);
-    println!("----     ----");
+    log::debug!("---- END ----\n{}\n----     ----", format_stacks(&stacks));
     let mut output = "".to_string();
     for stack in stacks {
         output.push(stack[stack.len()-1]);
@@ -225,10 +215,25 @@ fn solvep2(input: &str) -> String {
     output
 }
 
-pub fn run() {
-    let input = std::fs::read_to_string("data/d5.txt").expect("Failed to read input");
-    println!("part1: {}", solvep1(&input));
-    println!("part2: {}", solvep2(&input));
+#[derive(Default)]
+pub struct Day5;
+
+impl Solution for Day5 {
+    type Input = Procedure;
+    type Answer1 = String;
+    type Answer2 = String;
+
+    fn parse(&self, source: &str) -> Result<Procedure, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, procedure: &Procedure) -> String {
+        solvep1(procedure)
+    }
+
+    fn part2(&self, procedure: &Procedure) -> String {
+        solvep2(procedure)
+    }
 }
 
 
@@ -249,7 +254,24 @@ move 1 from 2 to 1
 move 3 from 1 to 3
 move 2 from 2 to 1
 move 1 from 1 to 2";
-        assert_eq!(solvep1(example_input), "CMZ");
-        assert_eq!(solvep2(example_input), "MCD");
+        let procedure = parse(example_input).unwrap();
+        assert_eq!(solvep1(&procedure), "CMZ");
+        assert_eq!(solvep2(&procedure), "MCD");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse("[A]\n 1 3\n").err().unwrap();
+        assert_eq!((err.line, err.column), (2, 4));
+        assert_eq!(err.expected, "stack label 2");
+
+        let err = parse("[A]\n 1\n\nmove 1 to 1\n").err().unwrap();
+        assert_eq!((err.line, err.column), (4, 8));
+
+        let err = parse("[A]\n 1\n\nmove 1 from 0 to 1\n").err().unwrap();
+        assert_eq!((err.line, err.column), (4, 13));
+        assert_eq!(err.expected, "a stack from 1 to 1");
+        let err = parse("[A]\n 1\n\nmove 1 from 1 to 2\n").err().unwrap();
+        assert_eq!((err.line, err.column), (4, 18));
     }
 }
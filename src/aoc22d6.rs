@@ -1,6 +1,9 @@
 
 
-fn any_same(buffer: &Vec<char>) -> Option<usize> {
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
+fn any_same(buffer: &[u8]) -> Option<usize> {
     for i in (0..(buffer.len()-1)).rev() {
         for j in ((i+1)..buffer.len()).rev() {
             if buffer[i] == buffer[j] {
@@ -12,8 +15,8 @@ fn any_same(buffer: &Vec<char>) -> Option<usize> {
 }
 
 fn advance(
-        it: &mut std::iter::Enumerate<std::str::Chars>,
-        buffer: &mut Vec<char>,
+        it: &mut std::iter::Enumerate<std::slice::Iter<u8>>,
+        buffer: &mut [u8],
         amount: usize)
     -> usize
 {
@@ -23,20 +26,48 @@ fn advance(
 
     let mut last_index = 0;
     for i in (buffer.len()-amount)..buffer.len() {
-        let (index, a) = it.next().unwrap();
+        let (index, a) = it.next().expect("Checked when parsing");
         last_index = index;
-        buffer[i] = a;
+        buffer[i] = *a;
     }
     return last_index;
 }
 
-fn solve(input: &str, length: usize) -> usize {
-    let mut it = input.chars().enumerate();
+/// Whether some run of length characters are all different
+fn has_marker(signal: &[u8], length: usize) -> bool {
+    signal.windows(length).any(|w| (1..length).all(|j| !w[..j].contains(&w[j])))
+}
+
+/// The signal is a single line of lowercase letters, which must contain a
+/// marker for both parts
+fn parse_signal(src: &str) -> Result<Vec<u8>, ParseError> {
+    let mut it = get_byte_iterator(src);
+    let signal = take_while(src, &mut it, |b| b.is_ascii_lowercase());
+    match it.peek() {
+        Some((_, b'\n')) | None => { },
+        Some(_) => {
+            return Err(error_at(src, &mut it, "a lowercase letter or end of line"));
+        }
+    }
+    while try_consume_sequence(src, &mut it, "\n")? { }
+    expect_end(src, &mut it)?;
+    for length in [4, 14] {
+        if !has_marker(signal.as_bytes(), length) {
+            return Err(ParseError::new(
+                src, signal.len(), &format!("a marker of {} different characters", length),
+                "end of signal"));
+        }
+    }
+    Ok(signal.as_bytes().to_vec())
+}
+
+fn solve(input: &[u8], length: usize) -> usize {
+    let mut it = input.iter().enumerate();
     let mut buffer = Vec::with_capacity(length);
-    buffer.push(' ');
+    buffer.push(b' ');
     for _ in 0..(length-1) {
-        let (_, a) = it.next().unwrap();
-        buffer.push(a);
+        let (_, a) = it.next().expect("Checked when parsing");
+        buffer.push(*a);
     }
     let mut safe_jump = 1;
     loop {
@@ -48,18 +79,33 @@ fn solve(input: &str, length: usize) -> usize {
     }
 }
 
-fn solvep1(input: &str) -> usize {
+fn solvep1(input: &[u8]) -> usize {
     solve(input, 4)
 }
 
-fn solvep2(input: &str) -> usize {
+fn solvep2(input: &[u8]) -> usize {
     solve(input, 14)
 }
 
-pub fn run() {
-    let input = std::fs::read_to_string("data/d6.txt").expect("Failed to read input");
-    println!("part1: {}", solvep1(&input));
-    println!("part2: {}", solvep2(&input));
+#[derive(Default)]
+pub struct Day6;
+
+impl Solution for Day6 {
+    type Input = Vec<u8>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Vec<u8>, ParseError> {
+        parse_signal(source)
+    }
+
+    fn part1(&self, signal: &Vec<u8>) -> usize {
+        solvep1(signal)
+    }
+
+    fn part2(&self, signal: &Vec<u8>) -> usize {
+        solvep2(signal)
+    }
 }
 
 
@@ -77,7 +123,7 @@ mod tests {
             ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11)];
 
         for (s, v) in examples {
-            assert_eq!(solvep1(s), v);
+            assert_eq!(solvep1(s.as_bytes()), v);
         }
     }
     
@@ -91,7 +137,18 @@ mod tests {
             ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 26)];
 
         for (s, v) in examples {
-            assert_eq!(solvep2(s), v);
+            assert_eq!(solvep2(s.as_bytes()), v);
         }
     }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(parse_signal("abcdefghijklmn\n").unwrap(), b"abcdefghijklmn");
+        let err = parse_signal("abcD\n").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 4));
+        let err = parse_signal("abcdefghijklma\n").unwrap_err();
+        assert_eq!(err.expected, "a marker of 14 different characters");
+        let err = parse_signal("").unwrap_err();
+        assert_eq!(err.expected, "a marker of 4 different characters");
+    }
 }
@@ -1,26 +1,30 @@
 
-struct DirObj<'a> {
+use crate::log;
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
+struct DirObj {
     listed: bool,
-    subs: Vec<Obj<'a>>,
+    subs: Vec<Obj>,
 }
 
 
-enum ObjType<'a> {
+enum ObjType {
     File(usize),
-    Dir(DirObj<'a>)
+    Dir(DirObj)
 }
 use ObjType::*;
 
 
-struct Obj<'a> {
-    name: &'a str,
-    obj: ObjType<'a>,
+pub struct Obj {
+    name: String,
+    obj: ObjType,
 }
 
-impl<'a> Obj<'a> {
-    fn new_dir(name: &'a str) -> Obj<'a> {
+impl Obj {
+    fn new_dir(name: &str) -> Obj {
         Obj {
-            name: name,
+            name: name.to_string(),
             obj: Dir(DirObj {
                 listed: false,
                 subs: Vec::new(),
@@ -28,9 +32,9 @@ impl<'a> Obj<'a> {
         }
     }
     
-    fn new_file(name: &'a str, size: usize) -> Obj<'a> {
+    fn new_file(name: &str, size: usize) -> Obj {
         Obj {
-            name: name,
+            name: name.to_string(),
             obj: File(size)
         }
     }
@@ -64,11 +68,11 @@ impl<'a> Obj<'a> {
     }
 }
 
-fn get_cwd_help<'a, 'b>(
+fn get_cwd_help<'b>(
         level: usize,
-        current_path: &Vec<&str>,
-        current_obj: &'b mut Obj<'a>)
-    -> &'b mut DirObj<'a>
+        current_path: &[String],
+        current_obj: &'b mut Obj)
+    -> &'b mut DirObj
 {
     match &mut current_obj.obj {
         File(_) => { panic!("Not a file"); },
@@ -76,9 +80,10 @@ fn get_cwd_help<'a, 'b>(
             if level == current_path.len() {
                 return dir_obj;
             } else {
-                let level_name = current_path[level];
+                let level_name = &current_path[level];
                 for obj in &mut dir_obj.subs {
-                    if obj.name == level_name {
+                    // A file could share the name, but can't be moved into
+                    if &obj.name == level_name && matches!(obj.obj, Dir(_)) {
                         return get_cwd_help(level + 1, current_path, obj);
                     }
                 }
@@ -91,67 +96,89 @@ fn get_cwd_help<'a, 'b>(
     }
 }
 
-fn get_cwd<'a, 'b>(current_path: &Vec<&str>, root: &'b mut Obj<'a>)
-    -> &'b mut DirObj<'a>
-{
+fn get_cwd<'b>(current_path: &[String], root: &'b mut Obj) -> &'b mut DirObj {
     get_cwd_help(0, current_path, root)
 }
 
-fn parse_input<'a>(input: &'a str) -> Obj<'a> {
+/// Parses the output of "ls" up to the next command
+fn parse_listing(src: &str, it: &mut ByteIterator, dir: &mut DirObj) -> Result<(), ParseError> {
+    while let Some((_, byte)) = it.peek() {
+        match byte {
+            b'$' => { break; },
+            b'\n' => {
+                it.next();
+                continue;
+            },
+            _ => { },
+        }
+        if optional(src, it, |src, it| expect_sequence(src, it, "dir ")).is_some() {
+            dir.subs.push(Obj::new_dir(word(src, it)?));
+        } else {
+            let size = parse_unsigned(src, it)?;
+            expect_sequence(src, it, " ")?;
+            dir.subs.push(Obj::new_file(word(src, it)?, size));
+        }
+        expect_line_end(src, it)?;
+    }
+    Ok(())
+}
+
+/// Rebuilds the directory tree from the terminal output
+///
+/// Only directories that have been listed can be moved into, so the tree
+/// always contains the current directory.
+fn parse_input(src: &str) -> Result<Obj, ParseError> {
     let mut root = Obj::new_dir("/");
 
-    let mut current_path = Vec::<&str>::new();
-    let mut it = input.split("\n").enumerate().peekable();
+    let mut current_path = Vec::<String>::new();
+    let mut it = get_byte_iterator(src);
     loop {
-        match it.next() {
-            Some((_, "")) => { },
-            Some((line_num, "$ ls")) => {
-                let current_dir = get_cwd(&current_path, &mut root);
-                if current_dir.listed {
-                    panic!("Listed same dir twice ({})", line_num);
-                }
-                current_dir.listed = true;
-                loop {
-                    match it.peek() {
-                        Some((_line_num, line)) => {
-                            if line == &"" {
-                                break;
-                            }
-                            let mut line_it = line.split(" ");
-                            let type_bit = line_it.next().unwrap();
-                            if type_bit == "$" {
-                                break;
-                            }
-                            let name_bit = line_it.next().unwrap();
-                            if type_bit == "dir" {
-                                current_dir.subs.push(Obj::new_dir(name_bit));
-                            } else {
-                                current_dir.subs.push(Obj::new_file(
-                                        name_bit, type_bit.parse().unwrap()));
-                            }
-                            it.next();
-                        }
-                        None => {
-                            break;
-                        }
-                    }
+        while try_consume_sequence(src, &mut it, "\n")? { }
+        let start = match it.peek() {
+            Some((index, _)) => *index,
+            None => { return Ok(root); },
+        };
+        expect_sequence(src, &mut it, "$ ")?;
+        if optional(src, &mut it, |src, it| expect_sequence(src, it, "ls")).is_some() {
+            expect_line_end(src, &mut it)?;
+            let current_dir = get_cwd(&current_path, &mut root);
+            if current_dir.listed {
+                return Err(ParseError::new(
+                    src, start, "a directory that hasn't been listed", "a second \"ls\""));
+            }
+            current_dir.listed = true;
+            parse_listing(src, &mut it, current_dir)?;
+            continue;
+        }
+
+        expect_sequence(src, &mut it, "cd ")?;
+        let name_start = match it.peek() {
+            Some((index, _)) => *index,
+            None => src.len(),
+        };
+        let folder = word(src, &mut it)?;
+        match folder {
+            ".." => {
+                if current_path.pop().is_none() {
+                    return Err(ParseError::new(
+                        src, name_start, "a directory below /", "\"..\" from /"));
                 }
             },
-            Some((_line_num, line)) => {
-                let mut line_it = line.split(" ");
-                assert_eq!(line_it.next().unwrap(), "$");
-                assert_eq!(line_it.next().unwrap(), "cd");
-                let folder = line_it.next().unwrap();
-                match folder {
-                    ".." => { current_path.pop().unwrap(); },
-                    "/" => { current_path.clear(); },
-                    val => { current_path.push(val); },
+            "/" => { current_path.clear(); },
+            val => {
+                let current_dir = get_cwd(&current_path, &mut root);
+                let found = current_dir.subs.iter()
+                    .any(|obj| obj.name == val && matches!(obj.obj, Dir(_)));
+                if !found {
+                    return Err(ParseError::new(
+                        src, name_start, "a directory listed in the current directory",
+                        &format!("\"{}\"", val)));
                 }
+                current_path.push(val.to_string());
             },
-            None => { break; },
         }
+        expect_line_end(src, &mut it)?;
     }
-    root
 }
 
 
@@ -212,17 +239,32 @@ fn solvep2(root: &Obj) -> usize {
     let (_, total_used) = walk_p1(root);
     let total_free = 70000000 - total_used;
     let target = 30000000 - total_free;
-    println!("Target: {}", target);
+    log::debug!("Target: {}", target);
     let (sol, _) = walk_p2(root, target);
     sol
 }
 
-pub fn run() {
-    let input = std::fs::read_to_string("data/d7.txt").expect("Failed to read input");
-    let root = parse_input(&input);
-    println!("{}", root.listing());
-    println!("part1: {}", solvep1(&root));
-    println!("part2: {}", solvep2(&root));
+#[derive(Default)]
+pub struct Day7;
+
+impl Solution for Day7 {
+    type Input = Obj;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Obj, ParseError> {
+        let root = parse_input(source)?;
+        log::debug!("{}", root.listing());
+        Ok(root)
+    }
+
+    fn part1(&self, root: &Obj) -> usize {
+        solvep1(root)
+    }
+
+    fn part2(&self, root: &Obj) -> usize {
+        solvep2(root)
+    }
 }
 
 
@@ -271,10 +313,26 @@ $ ls
     - d.ext (file, size=5626152)
     - k (file, size=7214296)
 ";
-        let root = parse_input(example);
+        let root = parse_input(example).unwrap();
         println!("{}", root.listing());
         assert_eq!(root.listing(), listing);
         assert_eq!(solvep1(&root), 95437);
         assert_eq!(solvep2(&root), 24933642);
     }
+
+    #[test]
+    fn test_parse_error() {
+        let err = parse_input("$ cd /\n$ ls\ndir a\n$ cd b\n").err().unwrap();
+        assert_eq!((err.line, err.column), (4, 6));
+        assert_eq!(err.expected, "a directory listed in the current directory");
+
+        let err = parse_input("$ cd /\n$ ls\n12 a\n$ ls\n").err().unwrap();
+        assert_eq!((err.line, err.column), (4, 1));
+
+        let root = parse_input("$ ls\n12 a\ndir a\n$ cd a\n$ ls\n5 b\n").unwrap();
+        assert_eq!(solvep1(&root), 22);
+
+        let err = parse_input("$ ls\nx a\n").err().unwrap();
+        assert_eq!((err.line, err.column), (2, 1));
+    }
 }
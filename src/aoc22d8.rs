@@ -5,173 +5,109 @@
 // 
 // It works so I'm going to leave it
 
-fn parse_input(input: &str) -> Vec<Vec<u8>> {
-    let mut output = Vec::new();
-    for line in input.split("\n") {
-        if line == "" { continue; }
-        let mut output_line = Vec::new();
-        for c in line.bytes() {
-            output_line.push((c - b'0')+1);
-        }
-        output.push(output_line);
-    }
-    output
+use crate::arr2d::Array2d;
+use crate::log;
+use crate::solution::Solution;
+use crate::parse_utils::ParseError;
+
+type Grid = Array2d<u8>;
+
+/// Tree heights, plus one so that zero is lower than any tree
+fn parse_input(input: &str) -> Result<Grid, ParseError> {
+    Array2d::try_from_text(input, |c| c.to_digit(10).map(|d| d as u8 + 1))
 }
 
-fn make_seen(grid: &Vec<Vec<u8>>) -> Vec<Vec<bool>> {
-    let mut seen = Vec::new();
-    for _ in 0..grid.len() {
-        let mut newnew = Vec::new();
-        for _ in 0..grid[0].len() {
-            newnew.push(false);
-        }
-        seen.push(newnew);
-    }
-    // top down
-    for i in 0..grid[0].len() {
-        let mut prev_height = 0;
-        for j in 0..grid.len() {
-            let v = grid[j][i];
-            if v > prev_height {
-                seen[j][i] = true;
-                prev_height = v;
-            }
-        }
-    }
-    for i in 0..grid[0].len() {
-        let mut prev_height = 0;
-        for j in (0..grid.len()).rev() {
-            let v = grid[j][i];
-            if v > prev_height {
-                seen[j][i] = true;
-                prev_height = v;
-            }
+/// Marks trees visible when looking along coords
+fn look_along(grid: &Grid, seen: &mut Array2d<bool>, coords: impl Iterator<Item=(isize,isize)>) {
+    let mut prev_height = 0;
+    for coord in coords {
+        let v = *grid.get(coord);
+        if v > prev_height {
+            *seen.get_mut(coord) = true;
+            prev_height = v;
         }
     }
-    for j in 0..grid.len() {
-        let mut prev_height = 0;
-        for i in 0..grid[0].len() {
-            let v = grid[j][i];
-            if v > prev_height {
-                seen[j][i] = true;
-                prev_height = v;
-            }
-        }
+}
+
+fn make_seen(grid: &Grid) -> Array2d<bool> {
+    let (size_i, size_j) = grid.size_i();
+    let mut seen = Array2d::new(false, size_i, size_j);
+    for i in 0..size_i {
+        // top down, then bottom up
+        look_along(grid, &mut seen, grid.col_coords(i));
+        look_along(grid, &mut seen, grid.col_coords(i).rev());
     }
-    for j in 0..grid.len() {
-        let mut prev_height = 0;
-        for i in (0..grid[0].len()).rev() {
-            let v = grid[j][i];
-            if v > prev_height {
-                seen[j][i] = true;
-                prev_height = v;
-            }
-        }
+    for j in 0..size_j {
+        look_along(grid, &mut seen, grid.row_coords(j));
+        look_along(grid, &mut seen, grid.row_coords(j).rev());
     }
     seen
 }
 
-fn solvep1(grid: &Vec<Vec<u8>>) -> usize {
+fn solvep1(grid: &Grid) -> usize {
     let seen = make_seen(grid);
-    let mut count = 0;
-    for j in 0..grid.len() {
-        for i in 0..grid[0].len() {
-            if seen[j][i] {
-                count += 1;
-            }
-        }
-    }
-    count
+    seen.coords().filter(|c| *seen.get(*c)).count()
 }
 
 
-fn calc_scenic(grid: &Vec<Vec<u8>>, ii: usize, jj: usize) -> usize {
-    let my_height = grid[jj][ii];
+fn calc_scenic(grid: &Grid, (ii, jj): (isize, isize)) -> usize {
+    let my_height = *grid.get((ii,jj));
     let mut score = 1;
 
-    let mut prev_height = 0;
-    let mut count = 0;
-    for i in (ii+1)..grid[0].len() {
-        let v = grid[jj][i];
-        count += 1;
-        if v>= my_height { break; }
-    }
-    if jj == 3 && ii ==2 { println!("{}", count); }
-    score = score * count;
-
-    let mut prev_height = 0;
-    let mut count = 0;
-    for i in (0..ii).rev() {
-        let v = grid[jj][i];
-        count += 1;
-        if v>= my_height { break; }
-    }
-    if jj == 3 && ii ==2 { println!("{}", count); }
-    score = score * count;
-
-    let mut prev_height = 0;
-    let mut count = 0;
-    for j in (jj+1)..grid[0].len() {
-        let v = grid[j][ii];
-        count += 1;
-        if v>= my_height { break; }
-    }
-    if jj == 3 && ii ==2 { println!("{}", count); }
-    score = score * count;
-
-    let mut prev_height = 0;
-    let mut count = 0;
-    for j in (0..jj).rev() {
-        let v = grid[j][ii];
-        count += 1;
-        if v>= my_height { break; }
+    for (di, dj) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+        let mut count = 0;
+        let mut coord = (ii+di, jj+dj);
+        while let Some(v) = grid.try_get(coord) {
+            count += 1;
+            if *v >= my_height { break; }
+            coord = (coord.0+di, coord.1+dj);
+        }
+        score *= count;
     }
-    if jj == 3 && ii ==2 { println!("{}", count); }
-    score = score * count;
 
     score
 }
 
-fn make_scenic(grid: &Vec<Vec<u8>>) -> Vec<Vec<usize>> {
-    let mut scenic = Vec::new();
-    for _ in 0..grid.len() {
-        let mut newnew = Vec::new();
-        for _ in 0..grid[0].len() {
-            newnew.push(0);
-        }
-        scenic.push(newnew);
-    }
-    for j in 0..grid.len() {
-        for i in 0..grid[0].len() {
-            scenic[j][i] = calc_scenic(grid, i, j);
-        }
+fn make_scenic(grid: &Grid) -> Array2d<usize> {
+    let (size_i, size_j) = grid.size_i();
+    let mut scenic = Array2d::new(0, size_i, size_j);
+    for coord in grid.coords() {
+        *scenic.get_mut(coord) = calc_scenic(grid, coord);
     }
     scenic
 }
 
-fn solvep2(grid: &Vec<Vec<u8>>) -> usize {
-    let seen = make_seen(grid);
+fn solvep2(grid: &Grid) -> usize {
     let scenic = make_scenic(grid);
-    println!("{:?}", scenic);
+    log::debug!("{:?}", scenic);
     let mut best = 0;
-    for j in 0..grid.len() {
-        for i in 0..grid[0].len() {
-            if scenic[j][i] > best {
-                best = scenic[j][i];
-            }
+    for coord in scenic.coords() {
+        if *scenic.get(coord) > best {
+            best = *scenic.get(coord);
         }
     }
     best
 }
 
-pub fn run() {
-    let input = std::fs::read_to_string("data/d8.txt").expect("Failed to read input");
-    let grid = parse_input(&input);
-    println!("{}", solvep1(&grid));
-    println!("{}", solvep2(&grid)); // 2100 is not right
-        //println!("{}", root.listing());
-    //    println!("part1: {}", solvep1(&root));
-        //println!("part2: {}", solvep2(&root));
+#[derive(Default)]
+pub struct Day8;
+
+impl Solution for Day8 {
+    type Input = Grid;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Grid, ParseError> {
+        parse_input(source)
+    }
+
+    fn part1(&self, grid: &Grid) -> usize {
+        solvep1(grid)
+    }
+
+    fn part2(&self, grid: &Grid) -> usize {
+        solvep2(grid) // 2100 is not right
+    }
 }
 
 
@@ -187,7 +123,7 @@ mod tests {
 65332
 33549
 35390";
-        let grid = parse_input(example);
+        let grid = parse_input(example).unwrap();
         assert_eq!(solvep1(&grid), 21);
         assert_eq!(solvep2(&grid), 8);
         assert!(false);
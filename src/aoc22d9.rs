@@ -1,71 +1,63 @@
 
 
-use std::collections::HashSet;
+use crate::log;
+use crate::solution::Solution;
+use crate::parse_utils::*;
+
+use crate::offset_grid::OffsetGrid;
 
 #[derive(Copy,Clone,Debug,Eq,PartialEq,Hash)]
-struct Vec2 {
+pub struct Vec2 {
     x: i32,
     y: i32,
 }
 
+impl Vec2 {
+    fn coord(&self) -> (isize, isize) {
+        (self.x as isize, self.y as isize)
+    }
+}
+
+
+/// A move is a direction vector and a distance
+pub type Move = (Vec2, usize);
 
 // Turns a text move command to a direction vector and a distance
-fn parse_move(line: &str) -> (Vec2, usize) {
-    let mut it = line.split(" ");
-    
-    let dir = match it.next() {
-        Some("U") => Vec2 {x: 0, y: 1},
-        Some("D") => Vec2 {x: 0, y:-1},
-        Some("L") => Vec2 {x:-1, y: 0},
-        Some("R") => Vec2 {x: 1, y: 0},
-        Some(s) => {
-            panic!("Unexpected direction: \"{}\"", s);
-        },
-        None => {
-            panic!("Missing direction");
-        },
-    };
-
-    let dist =
-        it.next()
-        .expect("Missing distance")
-        .parse()
-        .expect("Failed to parse distance");
-    (dir, dist)
+fn parse_move(src: &str, it: &mut ByteIterator) -> Result<Move, ParseError> {
+    let dir = one_of(src, it, &[
+        ("U", Vec2 {x: 0, y: 1}),
+        ("D", Vec2 {x: 0, y:-1}),
+        ("L", Vec2 {x:-1, y: 0}),
+        ("R", Vec2 {x: 1, y: 0}),
+    ])?;
+    expect_sequence(src, it, " ")?;
+    let dist = parse_unsigned(src, it)?;
+    Ok((dir, dist))
 }
 
-fn print_visitations(visitations: &HashSet<Vec2>) {
-    let mut xmin = 0;
-    let mut xmax = 0;
-    let mut ymin = 0;
-    let mut ymax = 0;
-    for Vec2 {x, y} in visitations {
-        if *x > xmax {
-            xmax = *x;
-        } if *x < xmin {
-            xmin = *x;
-        }
-        if *y > ymax {
-            ymax = *y;
-        } if *y < ymin {
-            ymin = *y;
-        }
-    }
-    for y in (ymin..(ymax+1)).rev() {
-        for x in xmin..(xmax+1) {
-            if visitations.contains(&Vec2{x:x, y:y}) {
-                print!("#")
+fn parse(source: &str) -> Result<Vec<Move>, ParseError> {
+    parse_lines(source, parse_move)
+}
+
+fn format_visitations(visitations: &OffsetGrid<bool>) -> String {
+    let ((xmin, ymin), (xmax, ymax)) = visitations.bounds();
+    let mut output = String::new();
+    for y in (ymin..ymax).rev() {
+        for x in xmin..xmax {
+            if *visitations.get((x, y)) {
+                output.push('#');
             } else {
-                print!(".")
+                output.push('.');
             }
         }
-        print!("\n");
+        output.push('\n');
     }
+    output
 }
 
 
-fn solve(input: &str, length: usize) -> usize {
-    let mut visitations: HashSet<Vec2> = HashSet::new();
+fn solve(moves: &Vec<Move>, length: usize) -> usize {
+    let mut visitations = OffsetGrid::new(false);
     
     let mut rope: Vec<Vec2> = Vec::with_capacity(length);
     for _ in 0..length {
@@ -74,12 +66,10 @@ fn solve(input: &str, length: usize) -> usize {
             y: 0,
         });
     }
-    visitations.insert(rope[length-1]);
+    *visitations.get_mut(rope[length-1].coord()) = true;
 
-    for line in input.split("\n") {
-        if line == "" { continue; }
-        let (dir, dist) = parse_move(line);
-        for _ in 0..dist {
+    for (dir, dist) in moves {
+        for _ in 0..*dist {
             rope[0].x += dir.x;
             rope[0].y += dir.y;
             for i in 0..(length-1) {
@@ -98,19 +88,34 @@ fn solve(input: &str, length: usize) -> usize {
                     }
                 }
             }
-            visitations.insert(rope[length-1]);
+            *visitations.get_mut(rope[length-1].coord()) = true;
         }
     }
     
-    print_visitations(&visitations);
-    visitations.len()
+    log::debug!("{}", format_visitations(&visitations));
+    visitations.iter().filter(|(_, visited)| **visited).count()
 }
 
 
-pub fn run() {
-    let input = std::fs::read_to_string("data/d9.txt").expect("Failed to read input");
-    println!("{}", solve(&input, 2));
-    println!("{}", solve(&input, 10));
+#[derive(Default)]
+pub struct Day9;
+
+impl Solution for Day9 {
+    type Input = Vec<Move>;
+    type Answer1 = usize;
+    type Answer2 = usize;
+
+    fn parse(&self, source: &str) -> Result<Vec<Move>, ParseError> {
+        parse(source)
+    }
+
+    fn part1(&self, moves: &Vec<Move>) -> usize {
+        solve(moves, 2)
+    }
+
+    fn part2(&self, moves: &Vec<Move>) -> usize {
+        solve(moves, 10)
+    }
 }
 
 
@@ -129,8 +134,8 @@ R 4
 D 1
 L 5
 R 2";
-        assert_eq!(solve(&example, 2), 13);
-        assert_eq!(solve(&example, 10), 1);
+        assert_eq!(solve(&parse(example).unwrap(), 2), 13);
+        assert_eq!(solve(&parse(example).unwrap(), 10), 1);
         let example2 =
             "R 5
 U 8
@@ -140,7 +145,7 @@ R 17
 D 10
 L 25
 U 20";
-        assert_eq!(solve(&example2, 10), 36);
+        assert_eq!(solve(&parse(example2).unwrap(), 10), 36);
         // assert!(false);
     }
 }
@@ -1,4 +1,6 @@
 
+use crate::parse_utils::{ParseError, ByteIterator, take_while, parse_lines};
+
 /// Two dimensional rectangular array of size (I x J)
 ///
 /// First dimension is the fastest moving index, so:
@@ -6,7 +8,7 @@
 ///  - Fast to expand along second dimension (J)
 ///
 /// Uses isize so we can do arithmetic around coordinates
-#[derive(Clone,PartialEq)]
+#[derive(Clone,Debug,PartialEq)]
 pub struct Array2d<T: Copy> {
     data: Vec<T>,
     size_i: isize,
@@ -67,26 +69,162 @@ impl<T: Copy> Array2d<T> {
         }
     }
 
+    #[allow(dead_code)]
+    pub fn in_bounds(&self, (i, j): (isize, isize)) -> bool {
+        0 <= i && i < self.size_i && 0 <= j && j < self.size_j
+    }
+
+    /// Panics if (i, j) is out of bounds
     #[allow(dead_code)]
     pub fn get(&self, (i, j): (isize, isize)) -> &T {
-        &self.data[i as usize + (self.size_i as usize)*(j as usize)]
+        match self.try_get((i, j)) {
+            Some(v) => v,
+            None => {
+                panic!("Index ({}, {}) out of bounds for Array2d of size {:?}", i, j, self.size_i());
+            }
+        }
     }
 
+    /// Returns None if (i, j) is out of bounds
+    #[allow(dead_code)]
+    pub fn try_get(&self, (i, j): (isize, isize)) -> Option<&T> {
+        if !self.in_bounds((i, j)) {
+            return None;
+        }
+        Some(&self.data[i as usize + (self.size_i as usize)*(j as usize)])
+    }
+
+    /// Panics if (i, j) is out of bounds
     #[allow(dead_code)]
     pub fn getu(&self, (i, j): (usize, usize)) -> &T {
+        assert!(i < self.size_i as usize && j < self.size_j as usize,
+            "Index ({}, {}) out of bounds for Array2d of size {:?}", i, j, self.size_i());
         &self.data[i + (self.size_i as usize)*j]
     }
 
+    /// Panics if (i, j) is out of bounds
     #[allow(dead_code)]
     pub fn get_mut(&mut self, (i, j): (isize, isize)) -> &mut T {
-        &mut self.data[i as usize + (self.size_i as usize)*(j as usize)]
+        let size = self.size_i();
+        match self.try_get_mut((i, j)) {
+            Some(v) => v,
+            None => {
+                panic!("Index ({}, {}) out of bounds for Array2d of size {:?}", i, j, size);
+            }
+        }
     }
 
+    /// Returns None if (i, j) is out of bounds
+    #[allow(dead_code)]
+    pub fn try_get_mut(&mut self, (i, j): (isize, isize)) -> Option<&mut T> {
+        if !self.in_bounds((i, j)) {
+            return None;
+        }
+        Some(&mut self.data[i as usize + (self.size_i as usize)*(j as usize)])
+    }
+
+    /// Panics if (i, j) is out of bounds
     #[allow(dead_code)]
     pub fn get_mutu(&mut self, (i, j): (usize, usize)) -> &mut T {
+        assert!(i < self.size_i as usize && j < self.size_j as usize,
+            "Index ({}, {}) out of bounds for Array2d of size {:?}", i, j, self.size_i());
         &mut self.data[i + (self.size_i as usize)*j]
     }
     
+    /// Every coordinate in the array, first dimension fastest
+    #[allow(dead_code)]
+    pub fn coords(&self) -> impl Iterator<Item=(isize, isize)> {
+        let (size_i, size_j) = self.size_i();
+        (0..size_j).flat_map(move |j| (0..size_i).map(move |i| (i, j)))
+    }
+
+    /// Coordinates along row j (varying i)
+    #[allow(dead_code)]
+    pub fn row_coords(&self, j: isize) -> impl DoubleEndedIterator<Item=(isize, isize)> {
+        (0..self.size_i).map(move |i| (i, j))
+    }
+
+    /// Coordinates along column i (varying j)
+    #[allow(dead_code)]
+    pub fn col_coords(&self, i: isize) -> impl DoubleEndedIterator<Item=(isize, isize)> {
+        (0..self.size_j).map(move |j| (i, j))
+    }
+
+    /// Orthogonal neighbours of (i, j) that are inside the array
+    #[allow(dead_code)]
+    pub fn neighbours4(&self, (i, j): (isize, isize)) -> impl Iterator<Item=(isize, isize)> {
+        let (size_i, size_j) = self.size_i();
+        [(-1, 0), (1, 0), (0, -1), (0, 1)].into_iter()
+            .map(move |(di, dj)| (i + di, j + dj))
+            .filter(move |&(i, j)| 0 <= i && i < size_i && 0 <= j && j < size_j)
+    }
+
+    /// Orthogonal and diagonal neighbours of (i, j) that are inside the array
+    #[allow(dead_code)]
+    pub fn neighbours8(&self, (i, j): (isize, isize)) -> impl Iterator<Item=(isize, isize)> {
+        let (size_i, size_j) = self.size_i();
+        [(-1,-1), (0,-1), (1,-1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)].into_iter()
+            .map(move |(di, dj)| (i + di, j + dj))
+            .filter(move |&(i, j)| 0 <= i && i < size_i && 0 <= j && j < size_j)
+    }
+
+    /// Builds an array from a character grid, one text line per row
+    ///
+    /// The first dimension runs along a line and the second down the lines.
+    /// Blank lines are skipped and every other line must be the same width.
+    #[allow(dead_code)]
+    pub fn from_text(src: &str, cell: impl Fn(char) -> T) -> Result<Array2d<T>, ParseError> {
+        Self::try_from_text(src, |c| Some(cell(c)))
+    }
+
+    /// As from_text, but cell returns None for characters that aren't allowed
+    #[allow(dead_code)]
+    pub fn try_from_text(src: &str, cell: impl Fn(char) -> Option<T>)
+        -> Result<Array2d<T>, ParseError>
+    {
+        let mut output = Array2d::new_empty();
+        parse_lines(src, |src, it: &mut ByteIterator| {
+            let start = match it.peek() {
+                Some((index, _)) => *index,
+                None => src.len(),
+            };
+            let line = take_while(src, it, |b| b != b'\n');
+            let mut row = Vec::with_capacity(line.len());
+            for (offset, c) in line.char_indices() {
+                match cell(c) {
+                    Some(v) => { row.push(v); },
+                    None => {
+                        return Err(ParseError::new(
+                            src, start + offset, "a valid grid cell", &format!("'{}'", c)));
+                    }
+                }
+            }
+            if output.size_j > 0 && row.len() != output.size_i as usize {
+                return Err(ParseError::new(
+                    src,
+                    start,
+                    &format!("a row of width {}", output.size_i),
+                    &format!("a row of width {}", row.len())));
+            }
+            output.add_row(row.into_iter());
+            Ok(())
+        })?;
+        Ok(output)
+    }
+
+    /// Renders the array as text, the reverse of from_text
+    #[allow(dead_code)]
+    pub fn to_text(&self, cell: impl Fn(T) -> char) -> String {
+        let mut output = String::with_capacity(((self.size_i + 1)*self.size_j) as usize);
+        for j in 0..self.size_j {
+            for coord in self.row_coords(j) {
+                output.push(cell(*self.get(coord)));
+            }
+            output.push('\n');
+        }
+        output
+    }
+
     /// Create a new row (add 1 to second dimension)
     /// 
     ///  - Fills with items in iterator
@@ -114,7 +252,7 @@ impl<T: Copy> Array2d<T> {
     ///  - Last row is the row to be dropped
     #[allow(dead_code)]
     pub fn drop_row(&mut self) {
-        let start = (self.size_i-1)*self.size_j;
+        let start = (self.size_j-1)*self.size_i;
         let end = self.size_i*self.size_j;
         self.data.drain(start as usize .. end as usize);
         self.size_j -= 1;
@@ -134,3 +272,88 @@ impl<T: Copy> Array2d<T> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds() {
+        let mut arr = Array2d::new(0, 3, 2);
+        *arr.get_mut((2,1)) = 5;
+        assert_eq!(arr.try_get((2,1)), Some(&5));
+        assert_eq!(arr.try_get((-1,1)), None);
+        assert_eq!(arr.try_get((3,0)), None);
+        assert_eq!(arr.try_get((0,2)), None);
+        assert_eq!(arr.try_get_mut((0,-1)), None);
+        assert!(arr.in_bounds((0,0)));
+        assert!(!arr.in_bounds((0,2)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_get_out_of_bounds() {
+        // Would alias (2,0) if not checked
+        let arr = Array2d::new(0, 3, 2);
+        arr.get((-1,1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_getu_out_of_bounds() {
+        // Would alias (0,1) if not checked
+        let arr = Array2d::new(0, 3, 2);
+        arr.getu((3,0));
+    }
+
+    #[test]
+    fn test_coords() {
+        let arr = Array2d::new(0, 3, 2);
+        let coords: Vec<_> = arr.coords().collect();
+        assert_eq!(coords, vec![(0,0), (1,0), (2,0), (0,1), (1,1), (2,1)]);
+        assert_eq!(arr.row_coords(1).collect::<Vec<_>>(), vec![(0,1), (1,1), (2,1)]);
+        assert_eq!(arr.col_coords(2).collect::<Vec<_>>(), vec![(2,0), (2,1)]);
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let arr = Array2d::new(0, 3, 3);
+        assert_eq!(arr.neighbours4((0,0)).collect::<Vec<_>>(), vec![(1,0), (0,1)]);
+        assert_eq!(arr.neighbours4((1,1)).count(), 4);
+        assert_eq!(arr.neighbours8((0,0)).collect::<Vec<_>>(), vec![(1,0), (0,1), (1,1)]);
+        assert_eq!(arr.neighbours8((1,1)).count(), 8);
+        assert_eq!(arr.neighbours8((2,1)).count(), 5);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut arr = Array2d::new_empty();
+        for j in 0..4 {
+            arr.add_row((0..3).map(|i| 10*j + i));
+        }
+        assert_eq!(arr.size_i(), (3, 4));
+        arr.drop_row();
+        assert_eq!(arr.size_i(), (3, 3));
+        assert_eq!(*arr.get((2,2)), 22);
+        arr.drop_front(2);
+        assert_eq!(arr.size_i(), (3, 1));
+        assert_eq!(*arr.get((0,0)), 20);
+    }
+
+    #[test]
+    fn test_text() {
+        let src = "#..\n.#.\n";
+        let arr = Array2d::from_text(src, |c| c == '#').unwrap();
+        assert_eq!(arr.size_i(), (3, 2));
+        assert!(*arr.get((1,1)));
+        assert!(!*arr.get((1,0)));
+        assert_eq!(arr.to_text(|v| if v { '#' } else { '.' }), src);
+
+        let err = Array2d::from_text("#..\n.#\n", |c| c == '#').unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.expected, "a row of width 3");
+
+        let err = Array2d::try_from_text("12\n3x\n", |c| c.to_digit(10)).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+    }
+}
@@ -24,20 +24,21 @@ impl<T: Copy, const N_DIM: usize> ArrayND<T, N_DIM> {
         Self::newu(initial, size_u)
     }
 
+    /// A dimension of size 0 gives an empty array, which has no valid index
     #[allow(dead_code)]
     pub fn newu(initial: T, size: [usize; N_DIM]) -> ArrayND<T,N_DIM> {
-        let mut count = 1;
+        let mut count: usize = 1;
         for i in 0..N_DIM {
             let s = size[i];
-            if usize::MAX / s < count {
-                panic!("Multi-dimensional array is too large");
-            }
             if s > (isize::MAX as usize) {
                 panic!(
                     "Multi-dimensional array dimension is too large (dimension {} is {})",
                     i, s);
             }
-            count *= s;
+            count = match count.checked_mul(s) {
+                Some(c) => c,
+                None => panic!("Multi-dimensional array is too large"),
+            };
         }
 
         let mut data = Vec::with_capacity(count);
@@ -70,6 +71,26 @@ impl<T: Copy, const N_DIM: usize> ArrayND<T, N_DIM> {
         &self.data[i]
     }
 
+    /// Returns None if index is out of bounds
+    #[allow(dead_code)]
+    pub fn try_get(&self, index: &[isize; N_DIM]) -> Option<&T> {
+        if !self.in_bounds(index) {
+            return None;
+        }
+        let i = self.calc_data_index(index);
+        Some(&self.data[i])
+    }
+
+    /// Returns None if index is out of bounds
+    #[allow(dead_code)]
+    pub fn try_get_mut(&mut self, index: &[isize; N_DIM]) -> Option<&mut T> {
+        if !self.in_bounds(index) {
+            return None;
+        }
+        let i = self.calc_data_index(index);
+        Some(&mut self.data[i])
+    }
+
     #[allow(dead_code)]
     pub fn getu(&self, index: &[usize; N_DIM]) -> &T {
         let i = self.calc_data_indexu(index);
@@ -88,6 +109,134 @@ impl<T: Copy, const N_DIM: usize> ArrayND<T, N_DIM> {
         &mut self.data[i]
     }
     
+    #[allow(dead_code)]
+    pub fn in_bounds(&self, index: &[isize; N_DIM]) -> bool {
+        index.iter().zip(self.size.iter()).all(|(&i, &size)| i >= 0 && (i as usize) < size)
+    }
+
+    /// Every element along with its coordinate, first dimension fastest
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item=([isize; N_DIM], &T)> {
+        self.data.iter().enumerate().map(move |(n, v)| (self.index_to_coord(n), v))
+    }
+
+    /// Coordinates that share a face with index and are in bounds
+    ///
+    /// There are up to 2*N_DIM of them, fewer on the edges of the array.
+    #[allow(dead_code)]
+    pub fn face_neighbours(&self, index: &[isize; N_DIM]) -> impl Iterator<Item=[isize; N_DIM]> + '_ {
+        let index = *index;
+        face_offsets::<N_DIM>().into_iter()
+            .map(move |offset| {
+                let mut n = index;
+                for i in 0..N_DIM {
+                    n[i] += offset[i];
+                }
+                n
+            })
+            .filter(move |n| self.in_bounds(n))
+    }
+
+    /// A view of the M_DIM dimensional slice through the array
+    ///
+    /// fixed has Some(position) for each dimension that is held still and
+    /// None for each of the M_DIM dimensions that are kept, so the 2D layer
+    /// k = 5 of a 3D array is `arr.slice::<2>([None, None, Some(5)])`.
+    #[allow(dead_code)]
+    pub fn slice<const M_DIM: usize>(&self, fixed: [Option<isize>; N_DIM])
+        -> ArrayNDView<'_, T, N_DIM, M_DIM>
+    {
+        let mut axes = [0; M_DIM];
+        let mut base = [0; N_DIM];
+        let mut n_free = 0;
+        for i in 0..N_DIM {
+            match fixed[i] {
+                Some(position) => {
+                    if position < 0 || position as usize >= self.size[i] {
+                        panic!(
+                            "Slice position {} out of bounds for dimension {} with size {}",
+                            position, i, self.size[i]);
+                    }
+                    base[i] = position;
+                },
+                None => {
+                    if n_free < M_DIM {
+                        axes[n_free] = i;
+                    }
+                    n_free += 1;
+                },
+            }
+        }
+        if n_free != M_DIM {
+            panic!("Slice keeps {} dimensions but asked for {}", n_free, M_DIM);
+        }
+        ArrayNDView {
+            array: self,
+            axes,
+            base,
+        }
+    }
+
+    /// Fills out from starts to every cell connected to them where fill holds
+    ///
+    /// Cells are connected if one is an offset in stencil away from the
+    /// other. Returns a mask that is true for every filled cell. Starts that
+    /// are out of bounds or don't satisfy fill are ignored.
+    ///
+    /// Uses an explicit stack rather than recursion, so large arrays are fine.
+    #[allow(dead_code)]
+    pub fn flood_fill(
+            &self,
+            starts: impl IntoIterator<Item=[isize; N_DIM]>,
+            stencil: &[[isize; N_DIM]],
+            fill: impl Fn(&T) -> bool)
+        -> ArrayND<bool, N_DIM>
+    {
+        let mut mask = ArrayND::newu(false, self.size);
+        let mut stack = Vec::new();
+        for start in starts {
+            stack.push(start);
+        }
+        while let Some(current) = stack.pop() {
+            if !self.in_bounds(&current) { continue; }
+            if *mask.get(&current) || !fill(self.get(&current)) { continue; }
+            *mask.get_mut(&current) = true;
+
+            for offset in stencil {
+                let mut next = current;
+                for i in 0..N_DIM {
+                    next[i] += offset[i];
+                }
+                stack.push(next);
+            }
+        }
+        mask
+    }
+
+    /// The coordinate of the nth element of the underlying storage
+    ///
+    /// The inverse of how coordinates are laid out, first dimension fastest.
+    #[allow(dead_code)]
+    pub fn index_to_coord(&self, data_index: usize) -> [isize; N_DIM] {
+        if data_index >= self.data.len() {
+            panic!(
+                "Out of bounds data index ({} for array of {} elements)",
+                data_index,
+                self.data.len());
+        }
+        self.index_to_coord_unchecked(data_index)
+    }
+
+    fn index_to_coord_unchecked(&self, data_index: usize) -> [isize; N_DIM] {
+        let mut coord = [0; N_DIM];
+        let mut remainder = data_index;
+        for (c, size) in coord.iter_mut().zip(self.size.iter()) {
+            *c = (remainder % size) as isize;
+            remainder /= size;
+        }
+        coord
+    }
+
     fn calc_data_index(&self, index: &[isize; N_DIM]) -> usize {
         let mut data_index = 0;
         let mut stride = 1;
@@ -111,7 +260,7 @@ impl<T: Copy, const N_DIM: usize> ArrayND<T, N_DIM> {
         let mut stride = 1;
         for i in 0..N_DIM {
             let dim_index = index[i];
-            if dim_index > self.size[i] as usize {
+            if dim_index >= self.size[i] {
                 panic!(
                     "Out of bounds access (element {} of dimension {} with size {})",
                     dim_index,
@@ -125,6 +274,88 @@ impl<T: Copy, const N_DIM: usize> ArrayND<T, N_DIM> {
     }
 }
 
+/// Offsets to the 2*N_DIM cells that share a face with a cell
+#[allow(dead_code)]
+pub fn face_offsets<const N_DIM: usize>() -> Vec<[isize; N_DIM]> {
+    let mut output = Vec::with_capacity(2*N_DIM);
+    for i in 0..N_DIM {
+        for delta in [-1, 1] {
+            let mut offset = [0; N_DIM];
+            offset[i] = delta;
+            output.push(offset);
+        }
+    }
+    output
+}
+
+/// A lower dimensional slice through an ArrayND, see ArrayND::slice
+pub struct ArrayNDView<'a, T: Copy, const N_DIM: usize, const M_DIM: usize> {
+    array: &'a ArrayND<T, N_DIM>,
+    /// Which dimension of the array each dimension of the view runs along
+    axes: [usize; M_DIM],
+    /// Coordinate of the view's origin in the array
+    base: [isize; N_DIM],
+}
+
+impl<'a, T: Copy, const N_DIM: usize, const M_DIM: usize> ArrayNDView<'a, T, N_DIM, M_DIM> {
+    fn to_array_index(&self, index: &[isize; M_DIM]) -> [isize; N_DIM] {
+        let mut array_index = self.base;
+        for i in 0..M_DIM {
+            array_index[self.axes[i]] = index[i];
+        }
+        array_index
+    }
+
+    #[allow(dead_code)]
+    pub fn size_i(&self) -> [isize; M_DIM] {
+        let array_size = self.array.size_i();
+        let mut size = [0; M_DIM];
+        for i in 0..M_DIM {
+            size[i] = array_size[self.axes[i]];
+        }
+        size
+    }
+
+    #[allow(dead_code)]
+    pub fn in_bounds(&self, index: &[isize; M_DIM]) -> bool {
+        let size = self.size_i();
+        (0..M_DIM).all(|i| 0 <= index[i] && index[i] < size[i])
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, index: &[isize; M_DIM]) -> &'a T {
+        if !self.in_bounds(index) {
+            panic!("Out of bounds access to slice ({:?} in {:?})", index, self.size_i());
+        }
+        self.array.get(&self.to_array_index(index))
+    }
+
+    /// Returns None if index is out of bounds
+    #[allow(dead_code)]
+    pub fn try_get(&self, index: &[isize; M_DIM]) -> Option<&'a T> {
+        if !self.in_bounds(index) {
+            return None;
+        }
+        Some(self.array.get(&self.to_array_index(index)))
+    }
+
+    /// Copies the slice out into its own array
+    #[allow(dead_code)]
+    pub fn to_array(&self) -> ArrayND<T, M_DIM> {
+        let size = self.axes.map(|axis| self.array.size[axis]);
+        let count = size.iter().product::<usize>();
+        let mut output = ArrayND {
+            data: Vec::with_capacity(count),
+            size,
+        };
+        for n in 0..count {
+            let index = output.index_to_coord_unchecked(n);
+            output.data.push(*self.get(&index));
+        }
+        output
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -162,5 +393,162 @@ mod tests {
             }
         }
     }
+
+    /// Visits every element through each way of indexing and checks they agree
+    fn check_indexing<const N_DIM: usize>(size: [usize; N_DIM]) {
+        let mut arr = ArrayND::newu(0, size);
+        let count = size.iter().product::<usize>();
+        for n in 0..count {
+            let coord = arr.index_to_coord(n);
+            assert!(arr.in_bounds(&coord));
+            assert_eq!(arr.calc_data_index(&coord), n);
+            *arr.try_get_mut(&coord).unwrap() = n;
+        }
+        for n in 0..count {
+            let coord = arr.index_to_coord(n);
+            let mut coord_u = [0; N_DIM];
+            for i in 0..N_DIM {
+                coord_u[i] = coord[i] as usize;
+            }
+            assert_eq!(*arr.get(&coord), n);
+            assert_eq!(*arr.getu(&coord_u), n);
+            assert_eq!(arr.try_get(&coord), Some(&n));
+        }
+
+        // Just past the end, and just before the start, of every dimension
+        for i in 0..N_DIM {
+            let mut coord = [0; N_DIM];
+            coord[i] = size[i] as isize;
+            assert!(!arr.in_bounds(&coord));
+            assert_eq!(arr.try_get(&coord), None);
+            coord[i] = -1;
+            assert!(!arr.in_bounds(&coord));
+            assert_eq!(arr.try_get_mut(&coord), None);
+        }
+    }
+
+    #[test]
+    fn test_indexing() {
+        check_indexing([7]);
+        check_indexing([3, 5]);
+        check_indexing([2, 3, 4]);
+        check_indexing([2, 3, 4, 5]);
+        check_indexing([1, 1, 1, 1]);
+        check_indexing([3, 0, 2]);
+        check_indexing([0]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let arr = ArrayND::new(0, [4, 0]);
+        assert_eq!(arr.size_u(), [4, 0]);
+        assert_eq!(arr.iter().count(), 0);
+        assert!(!arr.in_bounds(&[0, 0]));
+        assert_eq!(arr.try_get(&[3, 0]), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_getu_out_of_bounds() {
+        // Index equal to the size used to read the next row
+        let arr = ArrayND::newu(0, [3, 2]);
+        arr.getu(&[3, 0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_to_coord_out_of_bounds() {
+        let arr = ArrayND::newu(0, [3, 2]);
+        arr.index_to_coord(6);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut arr = ArrayND::new(0, [2, 3]);
+        *arr.get_mut(&[1, 2]) = 5;
+        let items: Vec<_> = arr.iter().collect();
+        assert_eq!(items.len(), 6);
+        assert_eq!(items[0], ([0, 0], &0));
+        assert_eq!(items[1], ([1, 0], &0));
+        assert_eq!(items[5], ([1, 2], &5));
+    }
+
+    #[test]
+    fn test_face_neighbours() {
+        assert_eq!(face_offsets::<1>(), vec![[-1], [1]]);
+        assert_eq!(face_offsets::<4>().len(), 8);
+
+        let arr = ArrayND::new(0, [3, 3, 3]);
+        assert_eq!(arr.face_neighbours(&[1, 1, 1]).count(), 6);
+        assert_eq!(arr.face_neighbours(&[0, 1, 1]).count(), 5);
+        assert_eq!(arr.face_neighbours(&[0, 0, 0]).count(), 3);
+        let mut around: Vec<_> = arr.face_neighbours(&[0, 0, 2]).collect();
+        around.sort();
+        assert_eq!(around, vec![[0, 0, 1], [0, 1, 2], [1, 0, 2]]);
+
+        let line = ArrayND::new(0, [4]);
+        assert_eq!(line.face_neighbours(&[3]).collect::<Vec<_>>(), vec![[2]]);
+    }
+
+    #[test]
+    fn test_slice() {
+        let mut arr = ArrayND::new(0, [2, 3, 4]);
+        for n in 0..24 {
+            let coord = arr.index_to_coord(n);
+            *arr.get_mut(&coord) = 100*coord[0] + 10*coord[1] + coord[2];
+        }
+
+        let layer = arr.slice::<2>([None, None, Some(3)]);
+        assert_eq!(layer.size_i(), [2, 3]);
+        assert_eq!(*layer.get(&[1, 2]), 123);
+        assert_eq!(layer.try_get(&[2, 0]), None);
+
+        let layer = arr.slice::<2>([Some(1), None, None]);
+        assert_eq!(layer.size_i(), [3, 4]);
+        assert_eq!(*layer.get(&[2, 3]), 123);
+        let copy = layer.to_array();
+        assert_eq!(*copy.get(&[0, 1]), 101);
+
+        let line = arr.slice::<1>([Some(0), Some(2), None]);
+        assert_eq!(line.size_i(), [4]);
+        assert_eq!(*line.get(&[1]), 21);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_wrong_dimension() {
+        let arr = ArrayND::new(0, [2, 3, 4]);
+        arr.slice::<2>([None, None, None]);
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        // A wall at i = 2 with a gap at j = 3
+        let mut arr = ArrayND::new(false, [5, 4]);
+        for j in 0..3 {
+            *arr.get_mut(&[2, j]) = true;
+        }
+        let faces = [[-1, 0], [1, 0], [0, -1], [0, 1]];
+        let filled = arr.flood_fill([[0, 0]], &faces, |wall| !wall);
+        let count = |mask: &ArrayND<bool,2>| mask.data.iter().filter(|v| **v).count();
+        assert_eq!(count(&filled), 17);
+        assert!(*filled.get(&[4, 0]));
+        assert!(!*filled.get(&[2, 0]));
+
+        // Close the gap and the far side can't be reached
+        *arr.get_mut(&[2, 3]) = true;
+        let filled = arr.flood_fill([[0, 0], [-1, 0], [2, 0]], &faces, |wall| !wall);
+        assert_eq!(count(&filled), 8);
+        assert!(!*filled.get(&[4, 0]));
+    }
+
+    #[test]
+    fn test_flood_fill_large() {
+        // Would overflow the stack if it recursed
+        let arr = ArrayND::new(0u8, [100, 100, 100]);
+        let faces = [[-1,0,0], [1,0,0], [0,-1,0], [0,1,0], [0,0,-1], [0,0,1]];
+        let filled = arr.flood_fill([[0,0,0]], &faces, |v| *v == 0);
+        assert!(*filled.get(&[99,99,99]));
+    }
 }
 
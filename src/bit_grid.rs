@@ -0,0 +1,197 @@
+
+/// Two dimensional grid of bools packed into 64 bit words
+///
+/// Each row (fixed j) is stored as whole words, with bit i of a row's words
+/// holding cell i, so a row can be tested or updated a word at a time.
+/// Bits past the width are always zero, so comparing or hashing grids
+/// compares only the cells.
+///
+/// Like Array2d, the first index runs along a row and rows can be added to
+/// the end or dropped from the front.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct BitGrid {
+    words: Vec<u64>,
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+}
+
+const WORD_BITS: usize = 64;
+
+impl BitGrid {
+    #[allow(dead_code)]
+    pub fn new(width: usize, height: usize) -> BitGrid {
+        let words_per_row = width.div_ceil(WORD_BITS);
+        BitGrid {
+            words: vec![0; words_per_row*height],
+            width,
+            height,
+            words_per_row,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[allow(dead_code)]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[allow(dead_code)]
+    pub fn words_per_row(&self) -> usize {
+        self.words_per_row
+    }
+
+    #[allow(dead_code)]
+    pub fn in_bounds(&self, (i, j): (isize, isize)) -> bool {
+        0 <= i && (i as usize) < self.width && 0 <= j && (j as usize) < self.height
+    }
+
+    /// Returns None if (i, j) is out of bounds
+    #[allow(dead_code)]
+    pub fn try_get(&self, (i, j): (isize, isize)) -> Option<bool> {
+        if !self.in_bounds((i, j)) {
+            return None;
+        }
+        let (i, j) = (i as usize, j as usize);
+        let word = self.words[j*self.words_per_row + i/WORD_BITS];
+        Some(word >> (i % WORD_BITS) & 1 == 1)
+    }
+
+    /// Panics if (i, j) is out of bounds
+    #[allow(dead_code)]
+    pub fn get(&self, (i, j): (isize, isize)) -> bool {
+        match self.try_get((i, j)) {
+            Some(v) => v,
+            None => {
+                panic!("Index ({}, {}) out of bounds for BitGrid of size {}x{}",
+                    i, j, self.width, self.height);
+            }
+        }
+    }
+
+    /// Panics if (i, j) is out of bounds
+    #[allow(dead_code)]
+    pub fn set(&mut self, (i, j): (isize, isize), value: bool) {
+        if !self.in_bounds((i, j)) {
+            panic!("Index ({}, {}) out of bounds for BitGrid of size {}x{}",
+                i, j, self.width, self.height);
+        }
+        let (i, j) = (i as usize, j as usize);
+        let word = &mut self.words[j*self.words_per_row + i/WORD_BITS];
+        let bit = 1 << (i % WORD_BITS);
+        if value {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    /// The words making up row j
+    #[allow(dead_code)]
+    pub fn row(&self, j: usize) -> &[u64] {
+        &self.words[j*self.words_per_row..(j+1)*self.words_per_row]
+    }
+
+    /// Mask with a bit set for every cell in a row, one word per row word
+    #[allow(dead_code)]
+    pub fn full_row(&self) -> Vec<u64> {
+        let mut output = vec![u64::MAX; self.words_per_row];
+        let spare_bits = self.words_per_row*WORD_BITS - self.width;
+        if spare_bits > 0 {
+            output[self.words_per_row - 1] = u64::MAX >> spare_bits;
+        }
+        output
+    }
+
+    /// Do any of the cells set in mask overlap those set in row j?
+    ///
+    /// Rows past the end of the grid are empty.
+    #[allow(dead_code)]
+    pub fn row_intersects(&self, j: usize, mask: &[u64]) -> bool {
+        if j >= self.height {
+            return false;
+        }
+        std::iter::zip(self.row(j), mask).any(|(a, b)| a & b != 0)
+    }
+
+    /// Sets every cell in row j that's set in mask
+    #[allow(dead_code)]
+    pub fn or_row(&mut self, j: usize, mask: &[u64]) {
+        let full = self.full_row();
+        let row = &mut self.words[j*self.words_per_row..(j+1)*self.words_per_row];
+        for ((word, m), f) in row.iter_mut().zip(mask).zip(full) {
+            *word |= m & f;
+        }
+    }
+
+    /// Adds an empty row to the end
+    #[allow(dead_code)]
+    pub fn add_row(&mut self) {
+        self.words.extend(std::iter::repeat_n(0, self.words_per_row));
+        self.height += 1;
+    }
+
+    /// Drops a set of rows from the front
+    #[allow(dead_code)]
+    pub fn drop_front(&mut self, nrows: usize) {
+        if nrows > self.height {
+            panic!("Asked to drop more rows than the grid has");
+        }
+        self.words.drain(0..nrows*self.words_per_row);
+        self.height -= nrows;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bit_grid() {
+        let mut grid = BitGrid::new(70, 3);
+        assert_eq!(grid.words_per_row(), 2);
+        grid.set((0, 0), true);
+        grid.set((69, 2), true);
+        grid.set((64, 1), true);
+        assert!(grid.get((0, 0)));
+        assert!(grid.get((69, 2)));
+        assert!(grid.get((64, 1)));
+        assert!(!grid.get((63, 1)));
+        assert_eq!(grid.try_get((70, 0)), None);
+        assert_eq!(grid.try_get((0, -1)), None);
+        grid.set((0, 0), false);
+        assert!(!grid.get((0, 0)));
+
+        assert_eq!(grid.full_row(), vec![u64::MAX, 0b111111]);
+        assert!(grid.row_intersects(1, &[0, 1]));
+        assert!(!grid.row_intersects(1, &[u64::MAX, 0b10]));
+        assert!(!grid.row_intersects(5, &[u64::MAX, u64::MAX]));
+
+        // Bits past the width are never set
+        grid.or_row(0, &[0b101, u64::MAX]);
+        assert_eq!(grid.row(0), &[0b101, 0b111111]);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut grid = BitGrid::new(7, 0);
+        for j in 0..4 {
+            grid.add_row();
+            grid.or_row(j, &[1 << j]);
+        }
+        assert_eq!(grid.height(), 4);
+        grid.drop_front(3);
+        assert_eq!(grid.height(), 1);
+        assert!(grid.get((3, 0)));
+
+        // Equal contents compare equal however they were built
+        let mut other = BitGrid::new(7, 1);
+        other.set((3, 0), true);
+        assert_eq!(grid, other);
+    }
+}
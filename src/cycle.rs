@@ -0,0 +1,123 @@
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A simulation whose state at step `start + period` is the same as at `start`
+///
+/// From then on it repeats forever, so the state at any later step is the
+/// same as at `equivalent_step`.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// The earliest step with the same state as step n
+    #[allow(dead_code)]
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.period
+        }
+    }
+
+    /// Extends a quantity that grows by a fixed amount each cycle to step n
+    ///
+    /// values[i] is the quantity at step i, for at least every step up to
+    /// `start + period`. Handy for heights, scores and the like.
+    #[allow(dead_code)]
+    pub fn extrapolate(&self, n: usize, values: &[isize]) -> isize {
+        let equivalent = self.equivalent_step(n);
+        if n < self.start {
+            return values[n];
+        }
+        let growth = values[self.start + self.period] - values[self.start];
+        let cycles = ((n - self.start) / self.period) as isize;
+        values[equivalent] + cycles*growth
+    }
+}
+
+/// Spots a repeat by remembering a fingerprint of every state seen
+///
+/// Feed it each state's fingerprint in step order. The fingerprint must
+/// capture everything that decides what happens next, for example (rock
+/// index, jet index, surface profile) for falling rocks.
+pub struct CycleDetector<K> {
+    seen: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq> CycleDetector<K> {
+    #[allow(dead_code)]
+    pub fn new() -> CycleDetector<K> {
+        CycleDetector {
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Records the state at step, returns the cycle if it's been seen before
+    #[allow(dead_code)]
+    pub fn observe(&mut self, step: usize, fingerprint: K) -> Option<Cycle> {
+        self.seen.insert(fingerprint, step).map(|start| Cycle { start, period: step - start })
+    }
+}
+
+/// Runs next from initial until a state repeats
+///
+/// For simulations that are a pure function of a small state. Returns the
+/// cycle along with every state up to the first repeat.
+#[allow(dead_code)]
+pub fn find_cycle<S: Clone + Hash + Eq>(initial: S, mut next: impl FnMut(&S) -> S)
+    -> (Cycle, Vec<S>)
+{
+    let mut detector = CycleDetector::new();
+    let mut states = vec![initial.clone()];
+    let mut state = initial;
+    let mut step = 0;
+    loop {
+        if let Some(cycle) = detector.observe(step, state.clone()) {
+            return (cycle, states);
+        }
+        state = next(&state);
+        states.push(state.clone());
+        step += 1;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cycle() {
+        // 0, 1, 2, 5, 26, 677 % 100 = 77, ... settles into a loop
+        let (cycle, states) = find_cycle(0u64, |x| (x*x + 1) % 100);
+        assert_eq!(states[cycle.start], states[cycle.start + cycle.period]);
+        for i in 0..(cycle.start + cycle.period) {
+            for j in (i+1)..(cycle.start + cycle.period) {
+                assert_ne!(states[i], states[j]);
+            }
+        }
+
+        // A plain loop of 5 after a lead in of 3
+        let (cycle, _) = find_cycle(0, |x| if *x < 7 { x + 1 } else { 3 });
+        assert_eq!(cycle, Cycle { start: 3, period: 5 });
+        assert_eq!(cycle.equivalent_step(2), 2);
+        assert_eq!(cycle.equivalent_step(8), 3);
+        assert_eq!(cycle.equivalent_step(1000), 3 + (1000 - 3) % 5);
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        // Grows by 1 per step, then 10 per 3 steps from step 2
+        let cycle = Cycle { start: 2, period: 3 };
+        let values = [0, 1, 2, 5, 9, 12];
+        assert_eq!(cycle.extrapolate(1, &values), 1);
+        assert_eq!(cycle.extrapolate(5, &values), 12);
+        assert_eq!(cycle.extrapolate(6, &values), 15);
+        assert_eq!(cycle.extrapolate(8, &values), 22);
+        assert_eq!(cycle.extrapolate(3 + 3*1000, &values), 5 + 10*1000);
+    }
+}
@@ -0,0 +1,136 @@
+
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides where input files are found
+pub const DATA_DIR_VAR: &str = "AOC22_DATA_DIR";
+
+const DEFAULT_DATA_DIR: &str = "data";
+
+/// Which input for a day we're after
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Variant {
+    /// Our personal puzzle input
+    Actual,
+    /// The example given in the puzzle text
+    Example,
+}
+
+#[derive(Debug)]
+pub enum InputError {
+    /// None of the candidate file names exist
+    Missing {
+        day: usize,
+        variant: Variant,
+        tried: Vec<PathBuf>,
+    },
+    /// A file was found but couldn't be read
+    Unreadable {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InputError::Missing { day, variant, tried } => {
+                let kind = match variant {
+                    Variant::Actual => "actual",
+                    Variant::Example => "example",
+                };
+                write!(f, "No {} input found for day {} (tried", kind, day)?;
+                for (i, path) in tried.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}", sep, path.display())?;
+                }
+                write!(f, ")")
+            },
+            InputError::Unreadable { path, error } => {
+                write!(f, "Failed to read {}: {}", path.display(), error)
+            },
+        }
+    }
+}
+
+/// Directory to look for inputs in
+///
+/// An explicit directory (from the command line) beats the environment
+/// variable, which beats the default of `data`.
+pub fn data_dir(explicit: Option<&str>) -> PathBuf {
+    match explicit {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::var(DATA_DIR_VAR) {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(DEFAULT_DATA_DIR),
+        },
+    }
+}
+
+/// File names that a day's input may be stored under, in preference order
+///
+/// Files have been named both `d17ex.txt` and `d1_ex.txt` over time, so
+/// accept either.
+fn candidate_names(day: usize, variant: Variant) -> [String; 2] {
+    match variant {
+        Variant::Actual => [format!("d{}.txt", day), format!("d{}_actual.txt", day)],
+        Variant::Example => [format!("d{}ex.txt", day), format!("d{}_ex.txt", day)],
+    }
+}
+
+/// Finds the file holding the input for a day
+pub fn find_input(data_dir: &Path, day: usize, variant: Variant) -> Result<PathBuf, InputError> {
+    let mut tried = Vec::new();
+    for name in candidate_names(day, variant) {
+        let path = data_dir.join(name);
+        if path.is_file() {
+            return Ok(path);
+        }
+        tried.push(path);
+    }
+    Err(InputError::Missing { day, variant, tried })
+}
+
+/// Reads the input for a day
+pub fn load_input(data_dir: &Path, day: usize, variant: Variant) -> Result<String, InputError> {
+    let path = find_input(data_dir, day, variant)?;
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Ok(source),
+        Err(error) => Err(InputError::Unreadable { path, error }),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_input() {
+        let dir = Path::new("data");
+        assert_eq!(find_input(dir, 17, Variant::Actual).unwrap(), dir.join("d17.txt"));
+        assert_eq!(find_input(dir, 17, Variant::Example).unwrap(), dir.join("d17ex.txt"));
+        assert_eq!(find_input(dir, 1, Variant::Actual).unwrap(), dir.join("d1_actual.txt"));
+        assert_eq!(find_input(dir, 1, Variant::Example).unwrap(), dir.join("d1_ex.txt"));
+    }
+
+    #[test]
+    fn test_missing_input() {
+        let dir = Path::new("data");
+        let err = find_input(dir, 42, Variant::Example).unwrap_err();
+        match &err {
+            InputError::Missing { day, tried, .. } => {
+                assert_eq!(*day, 42);
+                assert_eq!(tried, &vec![dir.join("d42ex.txt"), dir.join("d42_ex.txt")]);
+            },
+            _ => { panic!("Expected a missing input error"); }
+        }
+        assert_eq!(
+            err.to_string(),
+            "No example input found for day 42 (tried data/d42ex.txt, data/d42_ex.txt)");
+    }
+
+    #[test]
+    fn test_data_dir() {
+        assert_eq!(data_dir(Some("elsewhere")), PathBuf::from("elsewhere"));
+    }
+}
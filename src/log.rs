@@ -0,0 +1,79 @@
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How much diagnostic output to show
+///
+/// Diagnostics go to stderr, so stdout only ever has the answers. The level
+/// is set once by the runner, `-v` for Info and `-vv` for Debug.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Level {
+    /// Answers only
+    Quiet = 0,
+    /// Short summaries, like the cycle day 17 found
+    Info = 1,
+    /// Intermediate state, like grids, listings and stacks
+    Debug = 2,
+}
+
+impl Level {
+    /// The level for a number of `-v` flags
+    pub fn from_count(count: usize) -> Level {
+        match count {
+            0 => Level::Quiet,
+            1 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+}
+
+static LEVEL: AtomicUsize = AtomicUsize::new(Level::Quiet as usize);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Should messages at level be shown?
+pub fn enabled(level: Level) -> bool {
+    level as usize <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Prints to stderr when running with `-v`
+///
+/// The arguments aren't evaluated otherwise, so they can be costly to build.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Info) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints to stderr when running with `-vv`
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[allow(unused_imports)]
+pub(crate) use info;
+#[allow(unused_imports)]
+pub(crate) use debug;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(Level::from_count(0), Level::Quiet);
+        assert_eq!(Level::from_count(1), Level::Info);
+        assert_eq!(Level::from_count(7), Level::Debug);
+        assert!(Level::Info < Level::Debug);
+        // Answers are always shown
+        assert!(enabled(Level::Quiet));
+    }
+}
@@ -1,9 +1,243 @@
 
 mod arr2d;
 mod arr_nd;
+mod bit_grid;
+mod cycle;
+mod aoc22d1;
+mod aoc22d2;
+mod aoc22d3;
+mod aoc22d4;
+mod aoc22d5;
+mod aoc22d6;
+mod aoc22d7;
+mod aoc22d8;
+mod aoc22d9;
+mod aoc22d10;
+mod aoc22d11;
+mod aoc22d12;
+mod aoc22d13;
+mod aoc22d14;
+mod aoc22d15;
+mod aoc22d16;
+mod aoc22d17;
+mod aoc22d18;
+mod aoc22d19;
 mod aoc22d20;
+mod aoc22d21;
+mod aoc22d22;
+mod aoc22d23;
+mod aoc22d24;
+mod aoc22d25;
+mod input;
+mod log;
+mod offset_grid;
 mod parse_utils;
+mod search;
+mod solution;
+
+use std::path::Path;
+
+use input::Variant;
+use parse_utils::ParseError;
+use solution::Options;
+
+/// Which part(s) of a day's puzzle to run
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Part {
+    One,
+    Two,
+    Both,
+}
+
+impl Part {
+    /// Should part number `n` (1 or 2) be run?
+    pub fn includes(&self, n: usize) -> bool {
+        match self {
+            Part::One => n == 1,
+            Part::Two => n == 2,
+            Part::Both => true,
+        }
+    }
+}
+
+type RunFn = fn(&str, Part, Variant, &Options) -> Result<(), ParseError>;
+
+/// Every day we have, in calendar order
+const DAYS: [(usize, RunFn); 25] = [
+    (1, solution::run::<aoc22d1::Day1>),
+    (2, solution::run::<aoc22d2::Day2>),
+    (3, solution::run::<aoc22d3::Day3>),
+    (4, solution::run::<aoc22d4::Day4>),
+    (5, solution::run::<aoc22d5::Day5>),
+    (6, solution::run::<aoc22d6::Day6>),
+    (7, solution::run::<aoc22d7::Day7>),
+    (8, solution::run::<aoc22d8::Day8>),
+    (9, solution::run::<aoc22d9::Day9>),
+    (10, solution::run::<aoc22d10::Day10>),
+    (11, solution::run::<aoc22d11::Day11>),
+    (12, solution::run::<aoc22d12::Day12>),
+    (13, solution::run::<aoc22d13::Day13>),
+    (14, solution::run::<aoc22d14::Day14>),
+    (15, solution::run::<aoc22d15::Day15>),
+    (16, solution::run::<aoc22d16::Day16>),
+    (17, solution::run::<aoc22d17::Day17>),
+    (18, solution::run::<aoc22d18::Day18>),
+    (19, solution::run::<aoc22d19::Day19>),
+    (20, solution::run::<aoc22d20::Day20>),
+    (21, solution::run::<aoc22d21::Day21>),
+    (22, solution::run::<aoc22d22::Day22>),
+    (23, solution::run::<aoc22d23::Day23>),
+    (24, solution::run::<aoc22d24::Day24>),
+    (25, solution::run::<aoc22d25::Day25>),
+];
+
+fn usage() -> ! {
+    eprintln!("Usage: aoc22 [-v|-vv] [--example] [--data-dir DIR] [--explain]");
+    eprintln!("             [--dot DIR] [--frames DIR [--ppm SCALE]] [--rocks FILE]");
+    eprintln!("             <day|all> [part]");
+    eprintln!("  day         calendar day to run (1-25)");
+    eprintln!("  all         run every day we have");
+    eprintln!("  part        1 or 2, runs both parts if omitted");
+    eprintln!("  --example   use the example input from the puzzle text");
+    eprintln!("  --data-dir  where to find inputs (default ${} or data)", input::DATA_DIR_VAR);
+    eprintln!("  -v, -vv     show diagnostics on stderr, -vv for more");
+    eprintln!("  --explain   show how answers were reached on stderr (day 16)");
+    eprintln!("  --dot       write graphs as Graphviz DOT to DIR (day 16)");
+    eprintln!("  --frames    write animation frames to DIR (day 17 part 1)");
+    eprintln!("  --ppm       draw frames as PPM images, SCALE pixels per cell");
+    eprintln!("  --rocks     drop the rocks drawn in FILE instead (day 17)");
+    std::process::exit(1);
+}
+
+fn parse_part(arg: Option<String>) -> Part {
+    match arg.as_deref() {
+        None => Part::Both,
+        Some("1") => Part::One,
+        Some("2") => Part::Two,
+        Some(s) => {
+            eprintln!("Unknown part '{}'", s);
+            usage();
+        }
+    }
+}
+
+/// Loads the input for a day and runs it, returns false on failure
+fn run_day(day: usize, run: RunFn, data_dir: &Path, variant: Variant, part: Part, options: &Options)
+    -> bool
+{
+    match input::load_input(data_dir, day, variant) {
+        Ok(source) => {
+            match run(&source, part, variant, options) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("Failed to parse input for day {}: {}", day, e);
+                    false
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
 
 fn main() {
-    aoc22d20::run();
+    let mut variant = Variant::Actual;
+    let mut data_dir_arg = None;
+    let mut positional = Vec::new();
+    let mut verbosity = 0;
+    let mut options = Options::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--example" => { variant = Variant::Example; },
+            "--data-dir" => {
+                match args.next() {
+                    Some(dir) => { data_dir_arg = Some(dir); },
+                    None => { usage(); }
+                }
+            },
+            "--explain" => { options.explain = true; },
+            "--dot" => {
+                match args.next() {
+                    Some(dir) => { options.dot_dir = Some(dir.into()); },
+                    None => { usage(); }
+                }
+            },
+            "--frames" => {
+                match args.next() {
+                    Some(dir) => { options.frames_dir = Some(dir.into()); },
+                    None => { usage(); }
+                }
+            },
+            "--ppm" => {
+                match args.next().map(|scale| scale.parse()) {
+                    Some(Ok(scale)) if scale > 0 => { options.frame_scale = Some(scale); },
+                    _ => { usage(); }
+                }
+            },
+            "--rocks" => {
+                let path = match args.next() {
+                    Some(path) => path,
+                    None => { usage(); }
+                };
+                match std::fs::read_to_string(&path) {
+                    Ok(rocks) => { options.rocks = Some(rocks); },
+                    Err(e) => {
+                        eprintln!("Failed to read rocks from {}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "-v" => { verbosity += 1; },
+            "-vv" => { verbosity += 2; },
+            "-h" | "--help" => { usage(); },
+            _ => { positional.push(arg); }
+        }
+    }
+    log::set_level(log::Level::from_count(verbosity));
+    let data_dir = input::data_dir(data_dir_arg.as_deref());
+
+    let mut positional = positional.into_iter();
+    let day_arg = match positional.next() {
+        Some(v) => v,
+        None => { usage(); }
+    };
+    let part = parse_part(positional.next());
+    if positional.next().is_some() {
+        usage();
+    }
+
+    if day_arg == "all" {
+        let mut all_ok = true;
+        for (day, run) in DAYS {
+            println!("Day {}", day);
+            all_ok &= run_day(day, run, &data_dir, variant, part, &options);
+        }
+        if !all_ok {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let day: usize = match day_arg.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("Unknown day '{}'", day_arg);
+            usage();
+        }
+    };
+    match DAYS.iter().find(|(d, _)| *d == day) {
+        Some((_, run)) => {
+            if !run_day(day, *run, &data_dir, variant, part, &options) {
+                std::process::exit(1);
+            }
+        },
+        None => {
+            eprintln!("Day {} is not implemented", day);
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,142 @@
+
+use crate::arr2d::Array2d;
+
+/// Two dimensional grid with no fixed bounds
+///
+/// Coordinates may be negative. Cells that have never been written hold the
+/// default value, and writing outside the current storage grows it in that
+/// direction. Storage is an Array2d plus the coordinate of its (0,0) cell.
+#[derive(Clone,Debug)]
+pub struct OffsetGrid<T: Copy> {
+    data: Array2d<T>,
+    origin: (isize, isize),
+    default: T,
+}
+
+/// Smallest amount to grow by, so growing a cell at a time isn't slow
+const MIN_GROWTH: isize = 8;
+
+impl<T: Copy> OffsetGrid<T> {
+    #[allow(dead_code)]
+    pub fn new(default: T) -> OffsetGrid<T> {
+        OffsetGrid {
+            data: Array2d::new(default, 0, 0),
+            origin: (0, 0),
+            default,
+        }
+    }
+
+    /// The region that has storage, as (min, max) with max exclusive
+    ///
+    /// Everything written so far is inside it, but it may be bigger.
+    #[allow(dead_code)]
+    pub fn bounds(&self) -> ((isize, isize), (isize, isize)) {
+        let (size_i, size_j) = self.data.size_i();
+        let (oi, oj) = self.origin;
+        ((oi, oj), (oi + size_i, oj + size_j))
+    }
+
+    fn to_data(&self, (i, j): (isize, isize)) -> (isize, isize) {
+        (i - self.origin.0, j - self.origin.1)
+    }
+
+    /// The value at (i, j), the default if it's never been written
+    #[allow(dead_code)]
+    pub fn get(&self, coord: (isize, isize)) -> &T {
+        match self.data.try_get(self.to_data(coord)) {
+            Some(v) => v,
+            None => &self.default,
+        }
+    }
+
+    /// The value at (i, j), growing the grid to hold it if needed
+    #[allow(dead_code)]
+    pub fn get_mut(&mut self, coord: (isize, isize)) -> &mut T {
+        if !self.data.in_bounds(self.to_data(coord)) {
+            self.grow_to_include(coord);
+        }
+        let data_coord = self.to_data(coord);
+        self.data.get_mut(data_coord)
+    }
+
+    /// Every coordinate that has storage along with its value
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item=((isize, isize), &T)> {
+        let (oi, oj) = self.origin;
+        self.data.coords().map(move |c| ((c.0 + oi, c.1 + oj), self.data.get(c)))
+    }
+
+    fn grow_to_include(&mut self, (i, j): (isize, isize)) {
+        let ((mut min_i, mut min_j), (mut max_i, mut max_j)) = self.bounds();
+        let (size_i, size_j) = self.data.size_i();
+        // Grow by at least the current size so growth is amortised
+        let grow_i = std::cmp::max(size_i, MIN_GROWTH);
+        let grow_j = std::cmp::max(size_j, MIN_GROWTH);
+        if size_i == 0 || size_j == 0 {
+            // Nothing stored yet, start around (i, j)
+            (min_i, min_j, max_i, max_j) = (i, j, i + 1, j + 1);
+        }
+        if i < min_i { min_i = i - grow_i; }
+        if i >= max_i { max_i = i + 1 + grow_i; }
+        if j < min_j { min_j = j - grow_j; }
+        if j >= max_j { max_j = j + 1 + grow_j; }
+
+        let mut data = Array2d::new(self.default, max_i - min_i, max_j - min_j);
+        let (oi, oj) = self.origin;
+        for c in self.data.coords() {
+            *data.get_mut((c.0 + oi - min_i, c.1 + oj - min_j)) = *self.data.get(c);
+        }
+        self.data = data;
+        self.origin = (min_i, min_j);
+    }
+}
+
+impl<T: Copy + PartialEq> OffsetGrid<T> {
+    /// The smallest region holding every cell that isn't the default, as
+    /// (min, max) with max exclusive, None if there aren't any
+    #[allow(dead_code)]
+    pub fn used_bounds(&self) -> Option<((isize, isize), (isize, isize))> {
+        let mut used = self.iter().filter(|(_, v)| **v != self.default).map(|(c, _)| c);
+        let (i, j) = used.next()?;
+        let (mut min, mut max) = ((i, j), (i + 1, j + 1));
+        for (i, j) in used {
+            min = (min.0.min(i), min.1.min(j));
+            max = (max.0.max(i + 1), max.1.max(j + 1));
+        }
+        Some((min, max))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_grid() {
+        let mut grid = OffsetGrid::new(0);
+        assert_eq!(*grid.get((-100, 100)), 0);
+
+        *grid.get_mut((3, 4)) = 1;
+        *grid.get_mut((-5, 4)) = 2;
+        *grid.get_mut((3, -50)) = 3;
+        *grid.get_mut((1000, 1000)) = 4;
+        assert_eq!(*grid.get((3, 4)), 1);
+        assert_eq!(*grid.get((-5, 4)), 2);
+        assert_eq!(*grid.get((3, -50)), 3);
+        assert_eq!(*grid.get((1000, 1000)), 4);
+        assert_eq!(*grid.get((0, 0)), 0);
+
+        let ((min_i, min_j), (max_i, max_j)) = grid.bounds();
+        assert!(min_i <= -5 && min_j <= -50 && max_i > 1000 && max_j > 1000);
+
+        let mut written: Vec<_> = grid.iter().filter(|(_, v)| **v != 0).collect();
+        written.sort();
+        assert_eq!(written, vec![((-5, 4), &2), ((3, -50), &3), ((3, 4), &1), ((1000, 1000), &4)]);
+        assert_eq!(grid.used_bounds(), Some(((-5, -50), (1001, 1001))));
+
+        *grid.get_mut((1000, 1000)) = 0;
+        assert_eq!(grid.used_bounds(), Some(((-5, -50), (4, 5))));
+        assert_eq!(OffsetGrid::new(false).used_bounds(), None);
+    }
+}
@@ -2,6 +2,64 @@
 
 pub type ByteIterator<'a> = std::iter::Peekable<std::iter::Enumerate<std::str::Bytes<'a>>>;
 
+/// A recoverable parse failure, located in the source being parsed
+///
+/// Line and column are 1-based, offset is the 0-based byte offset into the
+/// source the iterator was created from.
+#[derive(Clone,Debug,PartialEq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl ParseError {
+    pub fn new(src: &str, offset: usize, expected: &str, found: &str) -> ParseError {
+        let before = &src.as_bytes()[..std::cmp::min(offset, src.len())];
+        let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+        let line_start = match before.iter().rposition(|b| *b == b'\n') {
+            Some(i) => i + 1,
+            None => 0,
+        };
+        ParseError {
+            offset,
+            line,
+            column: offset - line_start + 1,
+            expected: expected.to_string(),
+            found: found.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected {} but found {}",
+            self.line, self.column, self.expected, self.found)
+    }
+}
+
+/// Human readable form of a byte for error messages
+fn describe_byte(byte: Option<u8>) -> String {
+    match byte {
+        Some(b'\n') => "end of line".to_string(),
+        Some(b) if b.is_ascii_graphic() || b == b' ' => format!("'{}'", b as char),
+        Some(b) => format!("byte 0x{:02x}", b),
+        None => "end of input".to_string(),
+    }
+}
+
+/// Builds an error for whatever is next in the iterator
+pub fn error_at(src: &str, it: &mut ByteIterator, expected: &str) -> ParseError {
+    match it.peek() {
+        Some((index, byte)) => ParseError::new(src, *index, expected, &describe_byte(Some(*byte))),
+        None => ParseError::new(src, src.len(), expected, &describe_byte(None)),
+    }
+}
+
 #[allow(dead_code)]
 pub fn get_byte_iterator(source: &str) -> ByteIterator {
     source.bytes().enumerate().peekable()
@@ -25,91 +83,339 @@ pub fn consume_white_space(it: &mut ByteIterator) {
     }
 }
 
+/// Parses an optionally negative integer
 #[allow(dead_code)]
-pub fn parse_number(src: &str, it: &mut ByteIterator) -> isize {
-    let start = match it.peek() {
-        Some((index, val)) => {
-            if is_digit(*val) {
-                *index
-            } else if *val == b'-' {
-                // Handle negation here
-                it.next();
-                return -parse_number(src, it);
-            } else {
-                panic!("parse_number couldn't find the number");
-            }
-        },
-        None => {
-            panic!("parse_number couldn't find the number");
-        },
-    };
-    it.next();
-
-    let end = loop {
-        match it.peek() {
-            Some((index, val)) => {
-                if !is_digit(*val) {
-                    break *index;
-                }
-                it.next();
-            },
-            None => {
-                break usize::MAX;
-            }
-        }
-    };
-    
-    let substr = 
-        if end == usize::MAX {
-            &src[start..]
-        } else {
-            &src[start..end]
-        };
-
-    substr.parse().expect("Failed to parse number")
+pub fn try_parse_number(src: &str, it: &mut ByteIterator) -> Result<isize, ParseError> {
+    parse_signed(src, it)
 }
 
 
 /// Consumes sequence from iterator it
 ///
-/// If sequence is consumed, return true
-/// If first byte doesn't match, return false
-/// If any other byte doesn't match, panic
+/// If sequence is consumed, return Ok(true)
+/// If first byte doesn't match, return Ok(false)
+/// If any other byte doesn't match, return an error
 #[allow(dead_code)]
-pub fn consume_sequence(it: &mut ByteIterator, sequence: &str) -> bool {
+pub fn try_consume_sequence(src: &str, it: &mut ByteIterator, sequence: &str)
+    -> Result<bool, ParseError>
+{
     let mut seq_it = sequence.bytes();
     let seq_byte = seq_it.next().expect("sequence must be at least one character long");
     match it.peek() {
-        Some((_, byte)) => {
-            if *byte != seq_byte {
-                return false;
-            }
+        Some((_, byte)) if *byte == seq_byte => { it.next(); },
+        _ => { return Ok(false); }
+    };
+
+    let expected = format!("\"{}\"", sequence);
+    for seq_byte in seq_it {
+        match it.peek() {
+            Some((_, byte)) if *byte == seq_byte => { it.next(); },
+            _ => { return Err(error_at(src, it, &expected)); }
+        }
+    }
+    Ok(true)
+}
+
+/// Consumes sequence from iterator it, which must be present
+#[allow(dead_code)]
+pub fn expect_sequence(src: &str, it: &mut ByteIterator, sequence: &str) -> Result<(), ParseError> {
+    if try_consume_sequence(src, it, sequence)? {
+        Ok(())
+    } else {
+        Err(error_at(src, it, &format!("\"{}\"", sequence)))
+    }
+}
+
+/// Consumes a newline, or checks we're at the end of input
+#[allow(dead_code)]
+pub fn expect_line_end(src: &str, it: &mut ByteIterator) -> Result<(), ParseError> {
+    match it.peek() {
+        Some((_, b'\n')) => {
             it.next();
+            Ok(())
         },
-        None => {
-            return false;
+        None => Ok(()),
+        Some(_) => Err(error_at(src, it, "end of line")),
+    }
+}
+
+
+
+// Building blocks
+//
+// Each parser takes the whole source and an iterator over it, consumes what
+// it recognises and returns an error located at the first byte it couldn't
+// use. They are composed by passing them to each other, e.g.
+//
+//     separated(src, it, " -> ", |src, it| {
+//         let i = parse_signed::<isize>(src, it)?;
+//         expect_sequence(src, it, ",")?;
+//         Ok((i, parse_signed::<isize>(src, it)?))
+//     })
+
+/// Byte offset of the next byte, or the end of the source
+fn next_offset(src: &str, it: &mut ByteIterator) -> usize {
+    match it.peek() {
+        Some((index, _)) => *index,
+        None => src.len(),
+    }
+}
+
+/// Consumes bytes while pred holds, returning the (possibly empty) slice
+#[allow(dead_code)]
+pub fn take_while<'a>(src: &'a str, it: &mut ByteIterator, pred: impl Fn(u8) -> bool) -> &'a str {
+    let start = next_offset(src, it);
+    let mut end = start;
+    while let Some((index, byte)) = it.peek() {
+        if !pred(*byte) { break; }
+        end = *index + 1;
+        it.next();
+    }
+    &src[start..end]
+}
+
+fn parse_digits<T: std::str::FromStr>(src: &str, it: &mut ByteIterator, start: usize)
+    -> Result<T, ParseError>
+{
+    if take_while(src, it, is_digit).is_empty() {
+        return Err(error_at(src, it, "a number"));
+    }
+    let end = next_offset(src, it);
+    let text = &src[start..end];
+    match text.parse() {
+        Ok(v) => Ok(v),
+        Err(_) => {
+            let expected = format!("a number that fits in {}", std::any::type_name::<T>());
+            Err(ParseError::new(src, start, &expected, text))
         }
-    };
+    }
+}
+
+/// Parses an integer of any width, with an optional leading '-'
+#[allow(dead_code)]
+pub fn parse_signed<T: std::str::FromStr>(src: &str, it: &mut ByteIterator) -> Result<T, ParseError> {
+    let start = next_offset(src, it);
+    if let Some((_, b'-')) = it.peek() {
+        it.next();
+    }
+    parse_digits(src, it, start)
+}
+
+/// Parses an integer of any width made only of digits
+#[allow(dead_code)]
+pub fn parse_unsigned<T: std::str::FromStr>(src: &str, it: &mut ByteIterator) -> Result<T, ParseError> {
+    let start = next_offset(src, it);
+    parse_digits(src, it, start)
+}
+
+/// Parses a name made of letters, digits and '_' that doesn't start with a digit
+#[allow(dead_code)]
+pub fn identifier<'a>(src: &'a str, it: &mut ByteIterator) -> Result<&'a str, ParseError> {
+    match it.peek() {
+        Some((_, byte)) if byte.is_ascii_alphabetic() || *byte == b'_' => { },
+        _ => { return Err(error_at(src, it, "an identifier")); }
+    }
+    Ok(take_while(src, it, |b| b.is_ascii_alphanumeric() || b == b'_'))
+}
+
+/// Parses a run of anything other than white space
+#[allow(dead_code)]
+pub fn word<'a>(src: &'a str, it: &mut ByteIterator) -> Result<&'a str, ParseError> {
+    let word = take_while(src, it, |b| !b.is_ascii_whitespace());
+    if word.is_empty() {
+        return Err(error_at(src, it, "a word"));
+    }
+    Ok(word)
+}
 
-    let mut loc = 0;
+/// Consumes spaces (but not newlines)
+#[allow(dead_code)]
+pub fn spaces(it: &mut ByteIterator) {
+    while let Some((_, b' ')) = it.peek() {
+        it.next();
+    }
+}
+
+/// Runs parser, rewinding the iterator and returning None if it fails
+#[allow(dead_code)]
+pub fn optional<'a, T, F>(src: &'a str, it: &mut ByteIterator, mut parser: F) -> Option<T>
+    where F: FnMut(&'a str, &mut ByteIterator) -> Result<T, ParseError>
+{
+    let saved = it.clone();
+    match parser(src, it) {
+        Ok(v) => Some(v),
+        Err(_) => {
+            *it = saved;
+            None
+        }
+    }
+}
+
+/// Parses whichever of the literals comes next, returning its value
+#[allow(dead_code)]
+pub fn one_of<T: Copy>(src: &str, it: &mut ByteIterator, options: &[(&str, T)])
+    -> Result<T, ParseError>
+{
+    for (literal, value) in options {
+        if optional(src, it, |src, it| expect_sequence(src, it, literal)).is_some() {
+            return Ok(*value);
+        }
+    }
+    let names: Vec<String> = options.iter().map(|(l, _)| format!("\"{}\"", l)).collect();
+    Err(error_at(src, it, &format!("one of {}", names.join(", "))))
+}
+
+/// Parses one or more items separated by separator
+#[allow(dead_code)]
+pub fn separated<'a, T, F>(src: &'a str, it: &mut ByteIterator, separator: &str, mut item: F)
+    -> Result<Vec<T>, ParseError>
+    where F: FnMut(&'a str, &mut ByteIterator) -> Result<T, ParseError>
+{
+    let mut output = vec![item(src, it)?];
+    while try_consume_sequence(src, it, separator)? {
+        output.push(item(src, it)?);
+    }
+    Ok(output)
+}
+
+/// Checks there is nothing left to parse
+#[allow(dead_code)]
+pub fn expect_end(src: &str, it: &mut ByteIterator) -> Result<(), ParseError> {
+    match it.peek() {
+        None => Ok(()),
+        Some(_) => Err(error_at(src, it, "end of input")),
+    }
+}
+
+/// Parses every non-blank line of src with line_parser
+///
+/// line_parser must consume the whole line, but not the newline.
+#[allow(dead_code)]
+pub fn parse_lines<'a, T, F>(src: &'a str, mut line_parser: F) -> Result<Vec<T>, ParseError>
+    where F: FnMut(&'a str, &mut ByteIterator) -> Result<T, ParseError>
+{
+    let mut it = get_byte_iterator(src);
+    let mut output = Vec::new();
     loop {
-        let seq_byte = match seq_it.next() {
-            Some(v) => v,
-            None => { return true; },
-        };
-        let it_byte = match it.next() {
-            Some((_, byte)) => byte,
-            None => {
-                panic!("Unexpected end of input consuming sequence \"{}\"", sequence);
-            }
-        };
-        if seq_byte != it_byte {
-            panic!(
-                "Unexpected byte \"{}\" consuming sequence \"{}\" at position {}",
-                std::str::from_utf8(&[it_byte]).expect("non ascii byte in input"),
-                sequence,
-                loc);
+        // Skip blank lines
+        while try_consume_sequence(src, &mut it, "\n")? { }
+        if it.peek().is_none() {
+            return Ok(output);
         }
-        loc += 1;
+        output.push(line_parser(src, &mut it)?);
+        expect_line_end(src, &mut it)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        let src = "12,-7";
+        let mut it = get_byte_iterator(src);
+        assert_eq!(try_parse_number(src, &mut it), Ok(12));
+        assert_eq!(try_consume_sequence(src, &mut it, ","), Ok(true));
+        assert_eq!(try_parse_number(src, &mut it), Ok(-7));
+        assert_eq!(it.next(), None);
+    }
+
+    #[test]
+    fn test_parse_number_error() {
+        let src = "1,2\n3,x";
+        let mut it = get_byte_iterator(src);
+        try_parse_number(src, &mut it).unwrap();
+        expect_sequence(src, &mut it, ",").unwrap();
+        try_parse_number(src, &mut it).unwrap();
+        expect_line_end(src, &mut it).unwrap();
+        try_parse_number(src, &mut it).unwrap();
+        expect_sequence(src, &mut it, ",").unwrap();
+        let err = try_parse_number(src, &mut it).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                offset: 6,
+                line: 2,
+                column: 3,
+                expected: "a number".to_string(),
+                found: "'x'".to_string(),
+            });
+        assert_eq!(err.to_string(), "line 2, column 3: expected a number but found 'x'");
+    }
+
+    #[test]
+    fn test_consume_sequence_error() {
+        let src = "Valve AB";
+        let mut it = get_byte_iterator(src);
+        assert_eq!(try_consume_sequence(src, &mut it, "Tunnel"), Ok(false));
+        let err = try_consume_sequence(src, &mut it, "Values").unwrap_err();
+        assert_eq!((err.offset, err.line, err.column), (3, 1, 4));
+        assert_eq!(err.expected, "\"Values\"");
+        assert_eq!(err.found, "'v'");
+
+        let mut it = get_byte_iterator(src);
+        let err = expect_sequence(src, &mut it, "Valve AB, CD").unwrap_err();
+        assert_eq!(err.found, "end of input");
+    }
+
+    #[test]
+    fn test_integer_widths() {
+        let src = "255 256 -128 18446744073709551615";
+        let mut it = get_byte_iterator(src);
+        assert_eq!(parse_unsigned::<u8>(src, &mut it), Ok(255));
+        spaces(&mut it);
+        let err = parse_unsigned::<u8>(src, &mut it).unwrap_err();
+        assert_eq!(err.column, 5);
+        assert_eq!(err.expected, "a number that fits in u8");
+        assert_eq!(err.found, "256");
+        spaces(&mut it);
+        assert_eq!(parse_signed::<i8>(src, &mut it), Ok(-128));
+        spaces(&mut it);
+        assert_eq!(parse_unsigned::<u64>(src, &mut it), Ok(u64::MAX));
+        assert_eq!(expect_end(src, &mut it), Ok(()));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let src = "move 3 from a_1\n\nwait\nmove 10 from b2";
+        let lines = parse_lines(src, |src, it| {
+            if optional(src, it, |src, it| expect_sequence(src, it, "wait")).is_some() {
+                return Ok(None);
+            }
+            expect_sequence(src, it, "move ")?;
+            let n = parse_unsigned::<usize>(src, it)?;
+            expect_sequence(src, it, " from ")?;
+            Ok(Some((n, identifier(src, it)?)))
+        });
+        assert_eq!(lines, Ok(vec![Some((3, "a_1")), None, Some((10, "b2"))]));
+
+        let src = "498,4 -> 498,6 -> 496,6";
+        let mut it = get_byte_iterator(src);
+        let coords = separated(src, &mut it, " -> ", |src, it| {
+            let i = parse_signed::<isize>(src, it)?;
+            expect_sequence(src, it, ",")?;
+            Ok((i, parse_signed::<isize>(src, it)?))
+        });
+        assert_eq!(coords, Ok(vec![(498, 4), (498, 6), (496, 6)]));
+
+        let src = "1, 2, x";
+        let mut it = get_byte_iterator(src);
+        let err = separated(src, &mut it, ", ", parse_signed::<i32>).unwrap_err();
+        assert_eq!((err.column, err.found.as_str()), (7, "'x'"));
+
+        let src = "addx";
+        let mut it = get_byte_iterator(src);
+        assert_eq!(one_of(src, &mut it, &[("add", 1), ("addx", 2)]), Ok(1));
+        let mut it = get_byte_iterator(src);
+        assert_eq!(one_of(src, &mut it, &[("adds", 1), ("addx", 2)]), Ok(2));
+        let mut it = get_byte_iterator(src);
+        let err = one_of(src, &mut it, &[("noop", 1), ("x", 2)]).unwrap_err();
+        assert_eq!(err.expected, "one of \"noop\", \"x\"");
+
+        let src = "b.txt dir";
+        let mut it = get_byte_iterator(src);
+        assert_eq!(word(src, &mut it), Ok("b.txt"));
+        assert!(identifier(src, &mut it).is_err());
     }
 }
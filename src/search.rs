@@ -0,0 +1,239 @@
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+use std::hash::Hash;
+
+/// Cost of moving through a graph, steps for BFS
+pub type Cost = usize;
+
+/// What a search found
+///
+/// If the search stopped at a goal, only the distance to that goal (and the
+/// nodes along its path) is guaranteed to be the shortest. Otherwise every
+/// reachable node has its shortest distance.
+pub struct SearchResult<N> {
+    /// Cost to reach each node that was reached
+    pub distances: HashMap<N, Cost>,
+    /// The node each node was reached from, starts have none
+    predecessors: HashMap<N, N>,
+    /// The goal the search stopped at, if there was one
+    pub goal: Option<N>,
+}
+
+impl<N: Copy + Eq + Hash> SearchResult<N> {
+    fn new() -> SearchResult<N> {
+        SearchResult {
+            distances: HashMap::new(),
+            predecessors: HashMap::new(),
+            goal: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn distance(&self, node: N) -> Option<Cost> {
+        self.distances.get(&node).copied()
+    }
+
+    /// Cost to reach the goal, if one was found
+    #[allow(dead_code)]
+    pub fn goal_distance(&self) -> Option<Cost> {
+        self.goal.and_then(|goal| self.distance(goal))
+    }
+
+    /// Nodes from a start to node (inclusive), None if node wasn't reached
+    #[allow(dead_code)]
+    pub fn path_to(&self, node: N) -> Option<Vec<N>> {
+        if !self.distances.contains_key(&node) {
+            return None;
+        }
+        let mut path = vec![node];
+        let mut current = node;
+        while let Some(prev) = self.predecessors.get(&current) {
+            path.push(*prev);
+            current = *prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Breadth first search where every move costs 1
+///
+/// Searches out from all of starts at once and stops at the first node for
+/// which is_goal is true (use `|_| false` to get the whole distance map).
+#[allow(dead_code)]
+pub fn bfs<N, I>(
+        starts: impl IntoIterator<Item=N>,
+        mut neighbours: impl FnMut(N) -> I,
+        mut is_goal: impl FnMut(N) -> bool)
+    -> SearchResult<N>
+    where N: Copy + Eq + Hash, I: IntoIterator<Item=N>
+{
+    let mut result = SearchResult::new();
+    let mut queue = VecDeque::new();
+    for start in starts {
+        if let Entry::Vacant(e) = result.distances.entry(start) {
+            e.insert(0);
+            queue.push_back(start);
+        }
+    }
+
+    while let Some(current) = queue.pop_front() {
+        if is_goal(current) {
+            result.goal = Some(current);
+            break;
+        }
+        let dist = result.distances[&current];
+        for n in neighbours(current) {
+            if let Entry::Vacant(e) = result.distances.entry(n) {
+                e.insert(dist + 1);
+                result.predecessors.insert(n, current);
+                queue.push_back(n);
+            }
+        }
+    }
+    result
+}
+
+/// Shortest paths where neighbours gives each next node and the cost to move there
+#[allow(dead_code)]
+pub fn dijkstra<N, I>(
+        starts: impl IntoIterator<Item=N>,
+        neighbours: impl FnMut(N) -> I,
+        is_goal: impl FnMut(N) -> bool)
+    -> SearchResult<N>
+    where N: Copy + Eq + Hash, I: IntoIterator<Item=(N, Cost)>
+{
+    astar(starts, neighbours, |_| 0, is_goal)
+}
+
+/// Dijkstra guided towards the goal by heuristic
+///
+/// The heuristic must never overestimate the remaining cost to a goal, or the
+/// path found may not be the shortest.
+#[allow(dead_code)]
+pub fn astar<N, I>(
+        starts: impl IntoIterator<Item=N>,
+        mut neighbours: impl FnMut(N) -> I,
+        mut heuristic: impl FnMut(N) -> Cost,
+        mut is_goal: impl FnMut(N) -> bool)
+    -> SearchResult<N>
+    where N: Copy + Eq + Hash, I: IntoIterator<Item=(N, Cost)>
+{
+    let mut result = SearchResult::new();
+    // The heap holds (estimated total, cost so far, index into queued) so
+    // nodes don't have to be Ord
+    let mut queued = Vec::new();
+    let mut heap = BinaryHeap::new();
+    for start in starts {
+        if let Entry::Vacant(e) = result.distances.entry(start) {
+            e.insert(0);
+            heap.push(Reverse((heuristic(start), 0, queued.len())));
+            queued.push(start);
+        }
+    }
+
+    while let Some(Reverse((_, dist, index))) = heap.pop() {
+        let current = queued[index];
+        if dist > result.distances[&current] {
+            // Already found a quicker way here
+            continue;
+        }
+        if is_goal(current) {
+            result.goal = Some(current);
+            break;
+        }
+        for (n, step) in neighbours(current) {
+            let n_dist = dist + step;
+            let better = match result.distances.get(&n) {
+                Some(d) => n_dist < *d,
+                None => true,
+            };
+            if better {
+                result.distances.insert(n, n_dist);
+                result.predecessors.insert(n, current);
+                heap.push(Reverse((n_dist + heuristic(n), n_dist, queued.len())));
+                queued.push(n);
+            }
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 - 1 - 2
+    // |       |
+    // 3 - 4 - 5 - 6
+    const EDGES: [(usize, usize); 7] = [(0,1), (1,2), (0,3), (3,4), (4,5), (2,5), (5,6)];
+
+    fn graph_neighbours(node: usize) -> Vec<usize> {
+        let mut output = Vec::new();
+        for (a, b) in EDGES {
+            if a == node { output.push(b); }
+            if b == node { output.push(a); }
+        }
+        output
+    }
+
+    #[test]
+    fn test_bfs() {
+        let result = bfs([0], graph_neighbours, |_| false);
+        assert_eq!(result.goal, None);
+        assert_eq!(result.distance(6), Some(4));
+        assert_eq!(result.distance(4), Some(2));
+        assert_eq!(result.path_to(6).unwrap().len(), 5);
+        assert_eq!(result.path_to(0), Some(vec![0]));
+
+        let result = bfs([0], graph_neighbours, |n| n == 2);
+        assert_eq!(result.goal_distance(), Some(2));
+        assert_eq!(result.path_to(2), Some(vec![0, 1, 2]));
+
+        let result = bfs([0], graph_neighbours, |n| n == 7);
+        assert_eq!(result.goal, None);
+        assert_eq!(result.distance(7), None);
+        assert_eq!(result.path_to(7), None);
+    }
+
+    #[test]
+    fn test_multi_source() {
+        let result = bfs([0, 6], graph_neighbours, |_| false);
+        assert_eq!(result.distance(5), Some(1));
+        assert_eq!(result.distance(3), Some(1));
+        assert_eq!(result.path_to(4).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        // Going 0-1-2-5 is cheaper than 0-3-4-5 if 3-4 is expensive
+        let weighted = |node: usize| {
+            graph_neighbours(node).into_iter()
+                .map(move |n| (n, if (node, n) == (3, 4) || (node, n) == (4, 3) { 10 } else { 1 }))
+        };
+        let result = dijkstra([0], weighted, |n| n == 4);
+        assert_eq!(result.goal_distance(), Some(4));
+        assert_eq!(result.path_to(4), Some(vec![0, 1, 2, 5, 4]));
+    }
+
+    #[test]
+    fn test_astar() {
+        // Open 20x20 grid with a wall along x = 10 that has a gap at y = 19
+        let neighbours = |(x, y): (isize, isize)| {
+            [(-1, 0), (1, 0), (0, -1), (0, 1)].into_iter()
+                .map(move |(dx, dy)| (x + dx, y + dy))
+                .filter(|&(x, y)| (0..20).contains(&x) && (0..20).contains(&y))
+                .filter(|&(x, y)| x != 10 || y == 19)
+                .map(|n| (n, 1))
+        };
+        let goal = (19, 0);
+        let manhattan = |(x, y): (isize, isize)| ((goal.0 - x).abs() + (goal.1 - y).abs()) as Cost;
+        let result = astar([(0, 0)], neighbours, manhattan, |n| n == goal);
+        assert_eq!(result.goal_distance(), Some(19 + 2*19));
+        let expected = dijkstra([(0, 0)], neighbours, |n| n == goal);
+        assert_eq!(result.goal_distance(), expected.goal_distance());
+    }
+}
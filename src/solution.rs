@@ -0,0 +1,78 @@
+
+use std::path::PathBuf;
+
+use crate::Part;
+use crate::input::Variant;
+use crate::parse_utils::ParseError;
+
+/// Debugging options from the command line
+///
+/// Every day gets the same options and ignores the ones it has no use for.
+#[derive(Clone,Debug,Default)]
+pub struct Options {
+    /// Show how answers were reached, on stderr
+    pub explain: bool,
+    /// Write graphs of the puzzle here, as Graphviz DOT
+    pub dot_dir: Option<PathBuf>,
+    /// Write frames of an animation of the puzzle here
+    pub frames_dir: Option<PathBuf>,
+    /// Draw frames as PPM images with this many pixels per cell, rather
+    /// than as text
+    pub frame_scale: Option<usize>,
+    /// Drawings of rocks to drop instead of the puzzle's, see
+    /// aoc22d17::parse_rocks
+    pub rocks: Option<String>,
+}
+
+/// A day's puzzle, split into parsing and the two parts
+///
+/// The implementing type doubles as the day's config: any parameters that
+/// differ between the example and the actual input (the row to scan, the
+/// number of rounds, ...) are fields on it. `Default` gives the parameters
+/// for the actual input, `example` those for the example.
+pub trait Solution: Default {
+    /// Parsed form of the input, shared by both parts
+    type Input;
+    type Answer1: std::fmt::Display;
+    type Answer2: std::fmt::Display;
+
+    /// Config for the example in the puzzle text
+    ///
+    /// Only days whose example uses different parameters need to override
+    /// this.
+    fn example() -> Self {
+        Self::default()
+    }
+
+    /// Picks up any options the day supports, failing if one it uses
+    /// doesn't parse
+    fn configure(&mut self, _options: &Options) -> Result<(), ParseError> {
+        Ok(())
+    }
+
+    fn parse(&self, source: &str) -> Result<Self::Input, ParseError>;
+    fn part1(&self, input: &Self::Input) -> Self::Answer1;
+    fn part2(&self, input: &Self::Input) -> Self::Answer2;
+}
+
+/// Parse the source and print the answers for the requested parts
+///
+/// Runs with the config for the variant of input the source is, plus
+/// whatever the options turn on.
+pub fn run<S: Solution>(source: &str, part: Part, variant: Variant, options: &Options)
+    -> Result<(), ParseError>
+{
+    let mut solution = match variant {
+        Variant::Actual => S::default(),
+        Variant::Example => S::example(),
+    };
+    solution.configure(options)?;
+    let input = solution.parse(source)?;
+    if part.includes(1) {
+        println!("part1: {}", solution.part1(&input));
+    }
+    if part.includes(2) {
+        println!("part2: {}", solution.part2(&input));
+    }
+    Ok(())
+}